@@ -33,6 +33,7 @@ fuzz_target!(|data: &[u8]| {
             default_aa_code_hash: [0; 32],
             evm_interpreter_code_hash: [0; 32],
             hook_address: 0,
+            record_failure_backtraces: false,
         },
     );
     state.run();
@@ -0,0 +1,144 @@
+use crate::{callframe::Callframe, state::State, VirtualMachine};
+use u256::H160;
+
+/// The far calls that were being executed at some point, outermost first.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Backtrace(pub Vec<BacktraceFrame>);
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BacktraceFrame {
+    pub address: H160,
+    pub code_address: H160,
+    pub caller: H160,
+
+    /// The instruction being executed. For every frame but the innermost, this is a far call.
+    pub pc: u16,
+
+    /// The near calls that this frame was in, outermost first.
+    pub near_calls: Vec<BacktraceNearCall>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BacktraceNearCall {
+    pub call_instruction: u16,
+    pub exception_handler: u16,
+}
+
+impl Backtrace {
+    /// `pc` is the instruction being executed in the current frame.
+    pub(crate) fn capture(state: &State, pc: u16) -> Self {
+        Self(
+            state
+                .previous_frames
+                .iter()
+                .map(|(pc, frame)| BacktraceFrame::capture(frame, *pc))
+                .chain(std::iter::once(BacktraceFrame::capture(
+                    &state.current_frame,
+                    pc,
+                )))
+                .collect(),
+        )
+    }
+
+    /// True if this backtrace was taken in a near or far call made by the current frame
+    /// that has since returned.
+    fn is_inside_current_frame(&self, state: &State) -> bool {
+        let depth = state.previous_frames.len();
+        let Some(frame) = self.0.get(depth) else {
+            return false;
+        };
+
+        let current_near_calls = state.current_frame.near_call_sites().len();
+        let is_deeper = self.0.len() > depth + 1 || frame.near_calls.len() > current_near_calls;
+
+        self.0
+            .iter()
+            .zip(&state.previous_frames)
+            .all(|(b, (pc, f))| {
+                b.pc == *pc
+                    && b.is_of(f)
+                    && b.near_calls.len() == f.near_call_sites().len()
+                    && b.near_calls_start_with(f)
+            })
+            && frame.is_of(&state.current_frame)
+            && frame.near_calls_start_with(&state.current_frame)
+            && is_deeper
+    }
+}
+
+impl BacktraceFrame {
+    fn capture(frame: &Callframe, pc: u16) -> Self {
+        Self {
+            address: frame.address,
+            code_address: frame.code_address,
+            caller: frame.caller,
+            pc,
+            near_calls: frame
+                .near_call_sites()
+                .map(|(call_instruction, exception_handler)| BacktraceNearCall {
+                    call_instruction,
+                    exception_handler,
+                })
+                .collect(),
+        }
+    }
+
+    fn is_of(&self, frame: &Callframe) -> bool {
+        self.address == frame.address
+            && self.code_address == frame.code_address
+            && self.caller == frame.caller
+    }
+
+    fn near_calls_start_with(&self, frame: &Callframe) -> bool {
+        frame.near_call_sites().len() <= self.near_calls.len()
+            && self.near_calls.iter().zip(frame.near_call_sites()).all(
+                |(near_call, (call_instruction, exception_handler))| {
+                    near_call.call_instruction == call_instruction
+                        && near_call.exception_handler == exception_handler
+                },
+            )
+    }
+}
+
+impl VirtualMachine {
+    /// Where the failure that caused the most recent revert or panic originated.
+    ///
+    /// When a frame fails after a frame it called failed, the backtrace of the inner
+    /// failure is kept, as the outer one is most likely just propagating it.
+    /// Returning successfully from a frame discards the backtraces of failures inside it.
+    /// So after execution ends, this is only present if a failure made it to the initial frame.
+    ///
+    /// Always `None` unless [crate::Settings::record_failure_backtraces] is set.
+    pub fn failure_backtrace(&self) -> Option<&Backtrace> {
+        self.failure_backtrace.as_ref()
+    }
+
+    /// The far and near calls being executed, given that the current frame is at instruction `pc`.
+    pub fn backtrace(&self, pc: u16) -> Backtrace {
+        Backtrace::capture(&self.state, pc)
+    }
+
+    /// Called when the current frame fails at `pc`, before it is popped.
+    pub(crate) fn record_failure(&mut self, pc: u16) {
+        if self.settings.record_failure_backtraces
+            && !self
+                .failure_backtrace
+                .as_ref()
+                .is_some_and(|previous| previous.is_inside_current_frame(&self.state))
+        {
+            self.failure_backtrace = Some(Backtrace::capture(&self.state, pc));
+        }
+    }
+
+    /// Called when the current frame returns successfully, before it is popped.
+    pub(crate) fn record_success(&mut self) {
+        if self.settings.record_failure_backtraces
+            && self
+                .failure_backtrace
+                .as_ref()
+                .is_some_and(|previous| previous.is_inside_current_frame(&self.state))
+        {
+            self.failure_backtrace = None;
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Command line handling shared by the binaries.
+
+use std::collections::BTreeMap;
+use u256::{H160, U256};
+use vm2::{
+    decode::program_from_bytecode, initial_decommit, testworld::TestWorld, Program, VirtualMachine,
+};
+
+pub const OPTIONS_USAGE: &str = "\
+    --calldata HEX   calldata of the call, empty by default
+    --gas GAS        gas limit, u32::MAX by default
+    --caller ADDRESS caller of the contract, zero by default
+    --storage FILE   initial storage as JSON: {\"CONTRACT\": {\"KEY\": \"VALUE\", ...}, ...}
+    BYTECODE[@ADDRESS]...
+                     bytecode files to deploy, the first one is called.
+                     Addresses are assigned from 0x10000 unless given.";
+
+/// The contracts to deploy and how to call the first one.
+pub struct Options {
+    pub contracts: Vec<(H160, Program)>,
+    pub calldata: Vec<u8>,
+    pub gas: u32,
+    pub caller: H160,
+    pub storage: BTreeMap<(H160, U256), U256>,
+    /// The flags that weren't one of the above, for the binary to handle.
+    pub flags: Vec<String>,
+}
+
+impl Options {
+    /// Exits with `usage` if the arguments are invalid.
+    pub fn from_args(usage: &str) -> Self {
+        let mut options = Self {
+            contracts: vec![],
+            calldata: vec![],
+            gas: u32::MAX,
+            caller: H160::zero(),
+            storage: BTreeMap::new(),
+            flags: vec![],
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .unwrap_or_else(|| fail(&format!("{arg} expects a value")))
+            };
+            match arg.as_str() {
+                "--calldata" => {
+                    options.calldata =
+                        parse_hex(&value()).unwrap_or_else(|| fail("--calldata expects hex data"))
+                }
+                "--gas" => {
+                    options.gas = value()
+                        .parse()
+                        .unwrap_or_else(|_| fail("--gas expects a number"))
+                }
+                "--caller" => {
+                    options.caller = parse_address(&value())
+                        .unwrap_or_else(|| fail("--caller expects an address"))
+                }
+                "--storage" => {
+                    let path = value();
+                    let json = std::fs::read_to_string(&path)
+                        .unwrap_or_else(|error| fail(&format!("couldn't read {path}: {error}")));
+                    options.storage = parse_storage(&json).unwrap_or_else(|error| {
+                        fail(&format!("invalid storage in {path}: {error}"))
+                    })
+                }
+                "-h" | "--help" => fail(usage),
+                flag if flag.starts_with("--") => options.flags.push(arg),
+                _ => {
+                    let (path, address) = match arg.split_once('@') {
+                        Some((path, address)) => (
+                            path,
+                            parse_address(address).unwrap_or_else(|| fail("invalid address")),
+                        ),
+                        None => (
+                            arg.as_str(),
+                            H160::from_low_u64_be(0x10000 + options.contracts.len() as u64),
+                        ),
+                    };
+                    let bytecode = std::fs::read(path)
+                        .unwrap_or_else(|error| fail(&format!("couldn't read {path}: {error}")));
+                    options
+                        .contracts
+                        .push((address, program_from_bytecode(&bytecode, false)));
+                }
+            }
+        }
+
+        if options.contracts.is_empty() {
+            fail(usage);
+        }
+        options
+    }
+
+    /// Deploys the contracts and sets up a call to the first one.
+    pub fn into_vm_and_world(self) -> (VirtualMachine, TestWorld) {
+        let address = self.contracts[0].0;
+        let mut world = TestWorld::new(&self.contracts);
+        world.storage = self.storage;
+
+        let program = initial_decommit(&mut world, address);
+        let vm = VirtualMachine::new(
+            address,
+            program,
+            self.caller,
+            self.calldata,
+            self.gas,
+            vm2::Settings {
+                default_aa_code_hash: [0; 32],
+                evm_interpreter_code_hash: [0; 32],
+                hook_address: 0,
+                ..Default::default()
+            },
+        );
+        (vm, world)
+    }
+}
+
+pub fn parse_address(text: &str) -> Option<H160> {
+    let bytes = parse_hex(text)?;
+    (bytes.len() <= 20).then(|| H160::from_slice(&[vec![0; 20 - bytes.len()], bytes].concat()))
+}
+
+/// Accepts hex with or without `0x` and decimal numbers.
+pub fn parse_u256(text: &str) -> Option<U256> {
+    match text.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(text).ok(),
+    }
+}
+
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1)
+}
+
+/// Parses a JSON object that maps contract addresses to objects mapping keys to values.
+fn parse_storage(json: &str) -> Result<BTreeMap<(H160, U256), U256>, String> {
+    let contracts: BTreeMap<String, BTreeMap<String, String>> =
+        serde_json::from_str(json).map_err(|error| error.to_string())?;
+
+    let mut storage = BTreeMap::new();
+    for (contract, slots) in contracts {
+        let contract = parse_address(&contract).ok_or(format!("invalid address {contract}"))?;
+        for (key, value) in slots {
+            let key = parse_u256(&key).ok_or(format!("invalid key {key}"))?;
+            let value = parse_u256(&value).ok_or(format!("invalid value {value}"))?;
+            storage.insert((contract, key), value);
+        }
+    }
+    Ok(storage)
+}
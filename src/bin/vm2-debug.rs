@@ -0,0 +1,274 @@
+//! Runs bytecode in a [TestWorld] under an interactive debugger.
+//!
+//! Run with `--help` for the options. Type `help` at the prompt for a list of commands.
+
+use common::{fail, parse_address, Options, OPTIONS_USAGE};
+use std::io::{self, BufRead, Write};
+use vm2::{
+    hex, testworld::TestWorld, tracers::Debugger, ExecutionEnd, FatPointer, Step, VirtualMachine,
+};
+
+mod common;
+
+const HELP: &str = "\
+step [N]             execute N instructions, one by default
+continue             run until a breakpoint is hit or execution ends
+break PC [ADDRESS]   stop before instruction PC of the code at ADDRESS, the current code by default
+delete PC [ADDRESS]  remove a breakpoint
+registers            print registers and flags
+stack [SLOT [N]]     print N stack slots starting at SLOT, the eight below the stack pointer by default
+heap REGISTER        print the heap slice a fat pointer in a register points to
+heap PAGE START LEN  print a heap range
+storage              print storage changes
+events               print emitted events
+backtrace            print the far and near calls being executed
+quit";
+
+struct Session {
+    vm: VirtualMachine,
+    world: TestWorld,
+    debugger: Debugger,
+    /// The instruction to execute next in the current frame, or `None` once execution has ended.
+    pc: Option<u16>,
+}
+
+fn main() {
+    let usage = format!("usage: vm2-debug [OPTIONS] BYTECODE[@ADDRESS]...\n{OPTIONS_USAGE}");
+    let options = Options::from_args(&usage);
+    if let Some(flag) = options.flags.first() {
+        fail(&format!("unknown option {flag}\n{usage}"));
+    }
+    for (address, _) in &options.contracts {
+        println!("loaded {address:#x}");
+    }
+
+    let (vm, world) = options.into_vm_and_world();
+    let mut session = Session {
+        vm,
+        world,
+        debugger: Debugger::default(),
+        pc: Some(0),
+    };
+    session.print_location();
+
+    let stdin = io::stdin();
+    loop {
+        print!("(vm2) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some((&command, args)) = words.split_first() else {
+            continue;
+        };
+        if let Err(error) = session.execute(command, args) {
+            if error.is_empty() {
+                break;
+            }
+            println!("{error}");
+        }
+    }
+}
+
+impl Session {
+    /// An empty error means the session should end.
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<(), String> {
+        match command {
+            "s" | "step" => {
+                let count = args.first().map_or(Ok(1), |n| parse_number(n))?;
+                for _ in 0..count {
+                    if !self.step()? {
+                        break;
+                    }
+                }
+                self.print_location();
+            }
+            "c" | "continue" => {
+                // Steps over a breakpoint at the current instruction.
+                if self.step()? {
+                    let pc = self.pc.unwrap();
+                    match self.vm.resume_from(pc, &mut self.world, &mut self.debugger) {
+                        ExecutionEnd::StoppedByTracer { pc_to_resume_from } => {
+                            self.pc = Some(pc_to_resume_from);
+                            println!("hit {:?}", self.debugger.last_hit().unwrap());
+                        }
+                        end => self.end(end),
+                    }
+                }
+                self.print_location();
+            }
+            "b" | "break" | "delete" => {
+                let pc = parse_number(args.first().ok_or("expected a pc")?)?;
+                let address = match args.get(1) {
+                    Some(address) => parse_address(address).ok_or("invalid address")?,
+                    None => self.vm.state.current_frame.code_address,
+                };
+                if command == "delete" {
+                    self.debugger.remove_breakpoint(address, pc);
+                } else {
+                    self.debugger.add_breakpoint(address, pc);
+                }
+            }
+            "r" | "registers" => {
+                let state = &self.vm.state;
+                for (i, value) in state.registers.iter().enumerate() {
+                    let pointer = if state.register_pointer_flags & (1 << i) != 0 {
+                        " (pointer)"
+                    } else {
+                        ""
+                    };
+                    println!("r{i:<2} {value:#066x}{pointer}");
+                }
+                println!(
+                    "lt_of={} eq={} gt={}",
+                    state.flags.lt_of(),
+                    state.flags.eq(),
+                    state.flags.gt()
+                );
+            }
+            "stack" => {
+                let frame = &self.vm.state.current_frame;
+                let start = args
+                    .first()
+                    .map_or(Ok(frame.sp.saturating_sub(8)), |slot| parse_number(slot))?;
+                let count = args.get(1).map_or(Ok(8), |n| parse_number::<u16>(n))?;
+                for slot in start..start.saturating_add(count) {
+                    let pointer = if frame.stack.pointer_flags.get(slot) {
+                        " (pointer)"
+                    } else {
+                        ""
+                    };
+                    println!("{slot:>5} {:#066x}{pointer}", frame.stack.get(slot));
+                }
+            }
+            "heap" => {
+                let (page, start, length) = match args {
+                    [register] => {
+                        let index = register
+                            .trim_start_matches('r')
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|&i| i < self.vm.state.registers.len())
+                            .ok_or("expected a register")?;
+                        if self.vm.state.register_pointer_flags & (1 << index) == 0 {
+                            return Err(format!("r{index} doesn't hold a fat pointer"));
+                        }
+                        let pointer = FatPointer::from(self.vm.state.registers[index]);
+                        // Only the part from the offset to the end of the pointer is readable.
+                        let offset = pointer.offset.min(pointer.length);
+                        (
+                            pointer.memory_page,
+                            pointer.start.saturating_add(offset),
+                            pointer.length - offset,
+                        )
+                    }
+                    [page, start, length] => (
+                        parse_number(page)?,
+                        parse_number(start)?,
+                        parse_number(length)?,
+                    ),
+                    _ => return Err("expected a register or a page, start and length".into()),
+                };
+                let heap = self.vm.state.heaps.get(page).ok_or("no such heap")?;
+                // Bytes past the end of the heap's allocation read as zero.
+                let bytes = (start..start.saturating_add(length))
+                    .map(|i| heap.get(i as usize).copied().unwrap_or(0))
+                    .collect::<Vec<_>>();
+                for (i, line) in bytes.chunks(32).enumerate() {
+                    println!("{:#010x} {}", start as usize + i * 32, hex(line));
+                }
+            }
+            "storage" => {
+                for ((contract, key), (_, value)) in self.vm.world_diff.get_storage_changes() {
+                    println!("{contract:#x} {key:#x} = {value:#x}");
+                }
+            }
+            "events" => {
+                for event in self.vm.world_diff.events() {
+                    println!(
+                        "tx {} shard {} key {:#x} value {:#x}{}",
+                        event.tx_number,
+                        event.shard_id,
+                        event.key,
+                        event.value,
+                        if event.is_first { " (first)" } else { "" }
+                    );
+                }
+            }
+            "bt" | "backtrace" => {
+                let pc = self.pc.ok_or("execution has ended")?;
+                for (depth, frame) in self.vm.backtrace(pc).0.iter().enumerate() {
+                    println!(
+                        "#{depth} {:#x} (code {:#x}, caller {:#x}) at {}",
+                        frame.address, frame.code_address, frame.caller, frame.pc
+                    );
+                    for near_call in &frame.near_calls {
+                        println!(
+                            "    near call at {}, exception handler {}",
+                            near_call.call_instruction, near_call.exception_handler
+                        );
+                    }
+                }
+            }
+            "h" | "help" => println!("{HELP}"),
+            "q" | "quit" => return Err(String::new()),
+            _ => return Err(format!("unknown command {command}, try help")),
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction. Returns false if execution has ended.
+    fn step(&mut self) -> Result<bool, String> {
+        let pc = self.pc.ok_or("execution has ended")?;
+        loop {
+            match self.vm.step(pc, &mut self.world, &mut self.debugger) {
+                Step::Continue { pc, .. } => {
+                    self.pc = Some(pc);
+                    return Ok(true);
+                }
+                // The debugger stops at breakpoints but lets the retry through.
+                Step::End(ExecutionEnd::StoppedByTracer { .. }) => {}
+                Step::End(end) => {
+                    self.end(end);
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn end(&mut self, end: ExecutionEnd) {
+        self.pc = None;
+        match &end {
+            ExecutionEnd::ProgramFinished(data) => println!("finished with 0x{}", hex(data)),
+            ExecutionEnd::Reverted(data) => println!("reverted with 0x{}", hex(data)),
+            end => println!("{end:?}"),
+        }
+    }
+
+    fn print_location(&self) {
+        let Some(pc) = self.pc else {
+            return;
+        };
+        let frame = &self.vm.state.current_frame;
+        let instruction = &frame.program().instructions()[pc as usize];
+        println!(
+            "{:#x}:{pc} {} (gas {})",
+            frame.code_address,
+            instruction.mnemonic(),
+            frame.gas
+        );
+    }
+}
+
+fn parse_number<T: TryFrom<u64>>(text: &str) -> Result<T, String> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    number
+        .ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("invalid number {text}"))
+}
@@ -0,0 +1,160 @@
+//! Runs bytecode in a [vm2::testworld::TestWorld] and prints the outcome.
+//!
+//! Run with `--help` for the options.
+
+use common::{fail, Options, OPTIONS_USAGE};
+use std::fmt::Write;
+use vm2::{hex, tracers::write_json_string, ErrorRegistry, ExecutionEnd, World};
+
+mod common;
+
+fn main() {
+    let usage = format!(
+        "usage: vm2-run [OPTIONS] BYTECODE[@ADDRESS]...\n{OPTIONS_USAGE}\n    --json           print the outcome as JSON"
+    );
+    let mut options = Options::from_args(&usage);
+    let json = match std::mem::take(&mut options.flags).as_slice() {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        [flag, ..] => fail(&format!("unknown option {flag}\n{usage}")),
+    };
+    let gas_limit = options.gas;
+
+    let (mut vm, mut world) = options.into_vm_and_world();
+    let end = vm.run(&mut world, &mut ());
+    let gas_used = gas_limit - vm.state.current_frame.gas;
+
+    let storage_changes = vm
+        .world_diff
+        .get_storage_changes()
+        .into_iter()
+        .map(|((contract, key), (previous_value, value))| {
+            let previous_value =
+                previous_value.unwrap_or_else(|| world.read_storage(contract, key));
+            (contract, key, previous_value, value)
+        })
+        .collect::<Vec<_>>();
+    let events = vm.world_diff.events();
+    let logs = vm.world_diff.l2_to_l1_logs();
+
+    let (outcome, detail, return_data) = match &end {
+        ExecutionEnd::ProgramFinished(data) => ("finished", None, data.as_slice()),
+        ExecutionEnd::Reverted(data) => (
+            "reverted",
+            end.revert_reason(&ErrorRegistry::default())
+                .map(|reason| reason.to_string()),
+            data.as_slice(),
+        ),
+        ExecutionEnd::Panicked(reason) => ("panicked", Some(format!("{reason:?}")), &[][..]),
+        ExecutionEnd::SuspendedOnHook { hook, .. } => {
+            ("suspended", Some(format!("hook {hook}")), &[][..])
+        }
+        end => ("stopped", Some(format!("{end:?}")), &[][..]),
+    };
+
+    let mut out = String::new();
+    if json {
+        write!(out, r#"{{"outcome":"{outcome}""#).unwrap();
+        if let Some(detail) = &detail {
+            out.push_str(r#","detail":"#);
+            write_json_string(&mut out, detail);
+        }
+        write!(
+            out,
+            r#","returnData":"0x{}","gasUsed":{gas_used},"storageChanges":["#,
+            hex(return_data)
+        )
+        .unwrap();
+        for (i, (contract, key, previous_value, value)) in storage_changes.iter().enumerate() {
+            write!(
+                out,
+                r#"{}{{"contract":"{contract:#x}","key":"{key:#x}","previousValue":"{previous_value:#x}","value":"{value:#x}"}}"#,
+                separator(i)
+            )
+            .unwrap();
+        }
+        out.push_str(r#"],"events":["#);
+        for (i, event) in events.iter().enumerate() {
+            write!(
+                out,
+                r#"{}{{"key":"{:#x}","value":"{:#x}","isFirst":{},"shardId":{},"txNumber":{}}}"#,
+                separator(i),
+                event.key,
+                event.value,
+                event.is_first,
+                event.shard_id,
+                event.tx_number
+            )
+            .unwrap();
+        }
+        out.push_str(r#"],"l2ToL1Logs":["#);
+        for (i, log) in logs.iter().enumerate() {
+            write!(
+                out,
+                r#"{}{{"address":"{:#x}","key":"{:#x}","value":"{:#x}","isService":{},"shardId":{},"txNumber":{}}}"#,
+                separator(i),
+                log.address,
+                log.key,
+                log.value,
+                log.is_service,
+                log.shard_id,
+                log.tx_number
+            )
+            .unwrap();
+        }
+        out.push_str("]}");
+        println!("{out}");
+        return;
+    }
+
+    match detail {
+        Some(detail) => writeln!(out, "{outcome}: {detail}").unwrap(),
+        None => writeln!(out, "{outcome}").unwrap(),
+    }
+    writeln!(out, "return data: 0x{}", hex(return_data)).unwrap();
+    writeln!(out, "gas used: {gas_used}").unwrap();
+    writeln!(out, "storage changes: {}", storage_changes.len()).unwrap();
+    for (contract, key, previous_value, value) in &storage_changes {
+        writeln!(
+            out,
+            "  {contract:#x} {key:#x}: {previous_value:#x} -> {value:#x}"
+        )
+        .unwrap();
+    }
+    writeln!(out, "events: {}", events.len()).unwrap();
+    for event in events {
+        writeln!(
+            out,
+            "  tx {} shard {} key {:#x} value {:#x}{}",
+            event.tx_number,
+            event.shard_id,
+            event.key,
+            event.value,
+            if event.is_first { " (first)" } else { "" }
+        )
+        .unwrap();
+    }
+    writeln!(out, "L2 to L1 logs: {}", logs.len()).unwrap();
+    for log in logs {
+        writeln!(
+            out,
+            "  tx {} shard {} from {:#x} key {:#x} value {:#x}{}",
+            log.tx_number,
+            log.shard_id,
+            log.address,
+            log.key,
+            log.value,
+            if log.is_service { " (service)" } else { "" }
+        )
+        .unwrap();
+    }
+    print!("{out}");
+}
+
+fn separator(index: usize) -> &'static str {
+    if index == 0 {
+        ""
+    } else {
+        ","
+    }
+}
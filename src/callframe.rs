@@ -1,5 +1,5 @@
 use crate::{modified_world::Snapshot, program::Program, stack::Stack, Instruction};
-use u256::H160;
+use u256::{H160, U256};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Callframe {
@@ -25,6 +25,9 @@ pub struct Callframe {
     near_calls: Vec<NearCallFrame>,
 
     pub(crate) program: Program,
+    /// The hash the program was decommitted from. Not known for the initial frame,
+    /// as its program is passed to [crate::VirtualMachine::new] directly.
+    pub code_hash: Option<U256>,
 
     /// Returning a pointer to the calldata is illegal because it could result in
     /// the caller's heap being accessible both directly and via the fat pointer.
@@ -72,6 +75,7 @@ impl Callframe {
             code_address,
             caller,
             program,
+            code_hash: None,
             context_u128,
             is_static,
             stack,
@@ -120,10 +124,35 @@ impl Callframe {
         })
     }
 
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// The call instruction and exception handler of each near call, outermost first.
+    pub(crate) fn near_call_sites(&self) -> impl ExactSizeIterator<Item = (u16, u16)> + '_ {
+        self.near_calls
+            .iter()
+            .map(|f| (f.call_instruction, f.exception_handler))
+    }
+
+    pub(crate) fn is_in_near_call(&self) -> bool {
+        !self.near_calls.is_empty()
+    }
+
+    #[inline(always)]
     pub(crate) fn pc_to_u16(&self, pc: *const Instruction) -> u16 {
         unsafe { pc.offset_from(&self.program.instructions()[0]) as u16 }
     }
 
+    /// Like [Self::pc_to_u16] but returns `None` for instructions that are not part of this frame's program.
+    #[inline(always)]
+    pub(crate) fn pc_in_program(&self, pc: *const Instruction) -> Option<u16> {
+        let instructions = self.program.instructions().as_ptr_range();
+        instructions
+            .contains(&pc)
+            .then(|| unsafe { pc.offset_from(instructions.start) as u16 })
+    }
+
     pub(crate) fn pc_from_u16(&self, index: u16) -> Option<*const Instruction> {
         self.program
             .instructions()
@@ -4,13 +4,14 @@ use crate::{
         Immediate1, Immediate2, Register, Register1, Register2, RegisterAndImmediate,
         RelativeStack, Source, SourceWriter,
     },
-    instruction::{ExecutionEnd, InstructionResult},
+    instruction::{Handler, InstructionResult, PanicReason},
     instruction_handlers::{
-        Add, And, AuxHeap, CallingMode, Div, Heap, Mul, Or, PtrAdd, PtrPack, PtrShrink, PtrSub,
-        RotateLeft, RotateRight, ShiftLeft, ShiftRight, Sub, Xor,
+        free_panic, Add, And, AuxHeap, CallingMode, Div, Heap, Mul, Or, PtrAdd, PtrPack, PtrShrink,
+        PtrSub, RotateLeft, RotateRight, ShiftLeft, ShiftRight, Sub, Xor,
     },
-    jump_to_beginning, Instruction, Predicate, VirtualMachine, World,
+    jump_to_beginning, Instruction, Predicate, Program, Tracer, VirtualMachine, World,
 };
+use u256::U256;
 use zkevm_opcode_defs::{
     decoding::{EncodingModeProduction, VmEncodingMode},
     ImmMemHandlerFlags, Opcode,
@@ -20,6 +21,24 @@ use zkevm_opcode_defs::{
     SWAP_OPERANDS_FLAG_IDX_FOR_PTR_OPCODE, UMA_INCREMENT_FLAG_IDX,
 };
 
+/// Decodes a program from bytecode, which is a sequence of big-endian 64-bit instructions.
+/// The code page is the same bytecode read as 32-byte words.
+pub fn program_from_bytecode(bytecode: &[u8], is_bootloader: bool) -> Program {
+    Program::new(
+        decode_program(
+            &bytecode
+                .chunks_exact(8)
+                .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>(),
+            is_bootloader,
+        ),
+        bytecode
+            .chunks_exact(32)
+            .map(U256::from_big_endian)
+            .collect(),
+    )
+}
+
 pub fn decode_program(raw: &[u64], is_bootloader: bool) -> Vec<Instruction> {
     raw.iter()
         .take(1 << 16)
@@ -37,14 +56,22 @@ fn unimplemented_instruction(variant: Opcode) -> Instruction {
     let variant_as_number: u16 = unsafe { std::mem::transmute(variant) };
     Immediate1(variant_as_number).write_source(&mut arguments);
     Instruction {
-        handler: unimplemented_handler,
+        handler: Handler {
+            untraced: unimplemented_handler,
+            traced: |vm, instruction, world, tracer| {
+                unimplemented_handler(vm, instruction, world, tracer)
+            },
+        },
         arguments,
+        opcode: variant,
     }
 }
-fn unimplemented_handler(
+/// Panics the current frame, so the program can handle it like any other panic.
+fn unimplemented_handler<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
-    _: &mut dyn World,
+    world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     let variant: Opcode = unsafe {
         std::mem::transmute(
@@ -52,7 +79,13 @@ fn unimplemented_handler(
         )
     };
     eprintln!("Unimplemented instruction: {:?}!", variant);
-    Err(ExecutionEnd::Panicked)
+    free_panic(
+        vm,
+        instruction,
+        PanicReason::UnimplementedOpcode,
+        world,
+        tracer,
+    )
 }
 
 fn decode(raw: u64, is_bootloader: bool) -> Instruction {
@@ -336,3 +369,97 @@ fn decode(raw: u64, is_bootloader: bool) -> Instruction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{initial_decommit, testworld::TestWorld, ExecutionEnd, Settings};
+    use zkevm_opcode_defs::{ethereum_types::Address, ContextOpcode};
+
+    #[derive(Default)]
+    struct PanicRecorder(Vec<PanicReason>);
+
+    impl Tracer for PanicRecorder {
+        fn on_panic(&mut self, _: &VirtualMachine, reason: PanicReason) {
+            self.0.push(reason);
+        }
+    }
+
+    #[test]
+    fn unimplemented_opcode_only_panics_its_frame() {
+        let r0 = Register::new(0);
+        let r1 = Register::new(1);
+        let r2 = Register::new(2);
+
+        let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+        let callee_address = Address::from_low_u64_be(0xca11ee);
+
+        let mut abi = U256::zero();
+        abi.0[3] = 20_000;
+
+        let load_from_code_page = |immediate, register| {
+            Instruction::from_binop::<Add>(
+                CodePage(RegisterAndImmediate {
+                    immediate,
+                    register: r0,
+                })
+                .into(),
+                Register2(r0),
+                Register1(register).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                false,
+            )
+        };
+        let ret =
+            || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+        let main_program = Program::new(
+            vec![
+                load_from_code_page(0, r1),
+                load_from_code_page(1, r2),
+                Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                    Register1(r1),
+                    Register2(r2),
+                    Immediate1(4),
+                    false,
+                    Arguments::new(Predicate::Always, 200),
+                ),
+                Instruction::from_panic(None, Arguments::new(Predicate::Always, 5)),
+                ret(),
+            ],
+            vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+        );
+        let callee = Program::new(
+            vec![unimplemented_instruction(Opcode::Context(
+                ContextOpcode::AuxMutating0,
+            ))],
+            vec![],
+        );
+
+        let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+        let program = initial_decommit(&mut world, main_address);
+        let mut vm = VirtualMachine::new(
+            main_address,
+            program,
+            Address::zero(),
+            vec![],
+            100_000,
+            Settings {
+                default_aa_code_hash: [0; 32],
+                evm_interpreter_code_hash: [0; 32],
+                hook_address: 0,
+                ..Default::default()
+            },
+        );
+
+        // The caller continues at its exception handler.
+        let mut tracer = PanicRecorder::default();
+        assert_eq!(
+            vm.run(&mut world, &mut tracer),
+            ExecutionEnd::ProgramFinished(vec![])
+        );
+        assert_eq!(tracer.0, vec![PanicReason::UnimplementedOpcode]);
+    }
+}
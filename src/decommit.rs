@@ -13,14 +13,14 @@ impl WorldDiff {
         evm_interpreter_code_hash: [u8; 32],
         gas: &mut u32,
         is_constructor_call: bool,
-    ) -> Option<(Program, bool)> {
+    ) -> Option<(Program, U256, bool)> {
         let deployer_system_contract_address =
             Address::from_low_u64_be(DEPLOYER_SYSTEM_CONTRACT_ADDRESS_LOW as u64);
 
         let mut is_evm = false;
 
         let mut code_info = {
-            let (code_info, _) =
+            let (code_info, _, _) =
                 self.read_storage(world, deployer_system_contract_address, address);
             let mut code_info_bytes = [0; 32];
             code_info.to_big_endian(&mut code_info_bytes);
@@ -68,7 +68,7 @@ impl WorldDiff {
         };
 
         let program = world.decommit(code_key);
-        Some((program, is_evm))
+        Some((program, code_key, is_evm))
     }
 }
 
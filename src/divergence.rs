@@ -0,0 +1,229 @@
+use crate::{
+    tracers::{HeapWrite, StepRecord, StepRecorder},
+    ExecutionEnd, Step, VirtualMachine, World,
+};
+use std::{collections::VecDeque, fmt};
+use u256::{H160, U256};
+
+/// The first step where two executions differ.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    /// The number of steps both executions have in common.
+    pub step: usize,
+    pub difference: Difference,
+    /// The differing step of each execution. Missing if that execution ended without executing it.
+    pub left: Option<StepRecord>,
+    pub right: Option<StepRecord>,
+    /// The last steps before the divergence, oldest first. They are the same in both executions.
+    pub context: Vec<StepRecord>,
+}
+
+/// The first thing that differs in a step, checked in the order of the variants.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Difference {
+    /// Different instructions were executed.
+    Location,
+    /// The value or pointer flag of a register differs.
+    Register(usize),
+    Flags,
+    /// The first byte that was written differently. A write missing in one execution
+    /// counts as writing different bytes.
+    HeapByte {
+        heap: u32,
+        address: u32,
+    },
+    /// The first storage slot that was written differently.
+    StorageWrite {
+        contract: H160,
+        key: U256,
+    },
+    /// One execution ended before the other or both ended differently.
+    /// How an execution ended is only known when running in lockstep.
+    End {
+        left: Option<ExecutionEnd>,
+        right: Option<ExecutionEnd>,
+    },
+}
+
+/// Runs both VMs from their first instruction, one instruction at a time, until they diverge.
+/// Returns `None` if both executions end the same way without diverging.
+///
+/// Up to `context` steps before the divergence are included in the result.
+pub fn find_divergence(
+    left_vm: &mut VirtualMachine,
+    left_world: &mut dyn World,
+    right_vm: &mut VirtualMachine,
+    right_world: &mut dyn World,
+    context: usize,
+) -> Option<Divergence> {
+    let mut left_next = Step::Continue { pc: 0, frame: 0 };
+    let mut right_next = Step::Continue { pc: 0, frame: 0 };
+    let (mut left_recorder, mut right_recorder) = (StepRecorder::new(), StepRecorder::new());
+    let mut history = VecDeque::with_capacity(context);
+
+    for step in 0.. {
+        let (Step::Continue { pc: left_pc, .. }, Step::Continue { pc: right_pc, .. }) =
+            (&left_next, &right_next)
+        else {
+            return None;
+        };
+
+        left_next = left_vm.step(*left_pc, left_world, &mut left_recorder);
+        right_next = right_vm.step(*right_pc, right_world, &mut right_recorder);
+
+        // A step executes at most one instruction of the program. It executes none if
+        // execution ends before the instruction, for example because it is interrupted.
+        let left = left_recorder.take_steps().pop();
+        let right = right_recorder.take_steps().pop();
+
+        let difference = match (&left, &right) {
+            (Some(left), Some(right)) => compare_steps(left, right),
+            _ => None,
+        }
+        .or_else(|| match (&left_next, &right_next) {
+            (Step::Continue { .. }, Step::Continue { .. }) => None,
+            (Step::End(l), Step::End(r)) if l == r => None,
+            (l, r) => Some(Difference::End {
+                left: ended(l),
+                right: ended(r),
+            }),
+        });
+        if let Some(difference) = difference {
+            return Some(Divergence {
+                step,
+                difference,
+                left,
+                right,
+                context: history.into(),
+            });
+        }
+
+        if let Some(left) = left.filter(|_| context != 0) {
+            if history.len() == context {
+                history.pop_front();
+            }
+            history.push_back(left);
+        }
+    }
+    unreachable!()
+}
+
+/// Compares traces recorded with [StepRecorder]. Returns `None` if they are identical.
+///
+/// Up to `context` steps before the divergence are included in the result.
+pub fn compare_traces(
+    left: &[StepRecord],
+    right: &[StepRecord],
+    context: usize,
+) -> Option<Divergence> {
+    (0..left.len().max(right.len())).find_map(|step| {
+        let (l, r) = (left.get(step), right.get(step));
+        let difference = match (l, r) {
+            (Some(l), Some(r)) => compare_steps(l, r)?,
+            _ => Difference::End {
+                left: None,
+                right: None,
+            },
+        };
+        Some(Divergence {
+            step,
+            difference,
+            left: l.cloned(),
+            right: r.cloned(),
+            context: left[step.saturating_sub(context)..step].to_vec(),
+        })
+    })
+}
+
+fn compare_steps(left: &StepRecord, right: &StepRecord) -> Option<Difference> {
+    let location = |step: &StepRecord| (step.pc, step.frame, step.code_address, step.mnemonic);
+    if location(left) != location(right) {
+        return Some(Difference::Location);
+    }
+
+    let pointer_flags = left.register_pointer_flags ^ right.register_pointer_flags;
+    if let Some(i) =
+        (0..16).find(|&i| left.registers[i] != right.registers[i] || pointer_flags & (1 << i) != 0)
+    {
+        return Some(Difference::Register(i));
+    }
+
+    if left.flags != right.flags {
+        return Some(Difference::Flags);
+    }
+
+    if let Some((heap, address)) = first_heap_difference(&left.heap_writes, &right.heap_writes) {
+        return Some(Difference::HeapByte { heap, address });
+    }
+
+    let (l, r) = (&left.storage_writes, &right.storage_writes);
+    let i = (0..l.len().max(r.len())).find(|&i| l.get(i) != r.get(i))?;
+    let write = l.get(i).or(r.get(i)).unwrap();
+    Some(Difference::StorageWrite {
+        contract: write.contract,
+        key: write.key,
+    })
+}
+
+fn first_heap_difference(left: &[HeapWrite], right: &[HeapWrite]) -> Option<(u32, u32)> {
+    let i = (0..left.len().max(right.len())).find(|&i| left.get(i) != right.get(i))?;
+    match (left.get(i), right.get(i)) {
+        (Some(l), Some(r)) if (l.heap, l.address) == (r.heap, r.address) => {
+            let (mut l_bytes, mut r_bytes) = ([0; 32], [0; 32]);
+            l.value.to_big_endian(&mut l_bytes);
+            r.value.to_big_endian(&mut r_bytes);
+            let offset = l_bytes.iter().zip(&r_bytes).position(|(a, b)| a != b)?;
+            Some((l.heap, l.address + offset as u32))
+        }
+        (Some(write), _) | (None, Some(write)) => Some((write.heap, write.address)),
+        (None, None) => None,
+    }
+}
+
+fn ended(step: &Step) -> Option<ExecutionEnd> {
+    match step {
+        Step::End(end) => Some(end.clone()),
+        Step::Continue { .. } => None,
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "diverged after {} steps: ", self.step)?;
+        match &self.difference {
+            Difference::Location => writeln!(f, "different instructions")?,
+            Difference::Register(i) => writeln!(f, "r{i} differs")?,
+            Difference::Flags => writeln!(f, "flags differ")?,
+            Difference::HeapByte { heap, address } => {
+                writeln!(f, "heap {heap} differs at byte {address:#x}")?
+            }
+            Difference::StorageWrite { contract, key } => {
+                writeln!(f, "write to slot {key:#x} of {contract:#x} differs")?
+            }
+            Difference::End { left, right } => writeln!(f, "ended with {left:?} and {right:?}")?,
+        }
+        for step in &self.context {
+            writeln!(f, "  {}", StepLine(step))?;
+        }
+        for (side, step) in [("left", &self.left), ("right", &self.right)] {
+            match step {
+                Some(step) => writeln!(f, "{side}: {}", StepLine(step))?,
+                None => writeln!(f, "{side}: ended")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct StepLine<'a>(&'a StepRecord);
+
+impl fmt::Display for StepLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = self.0;
+        write!(
+            f,
+            "frame {} {:#x}:{} {}",
+            step.frame, step.code_address, step.pc, step.mnemonic
+        )
+    }
+}
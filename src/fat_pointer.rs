@@ -1,7 +1,8 @@
 use u256::U256;
 
+/// A pointer to a slice of a heap, as stored in a register or stack slot with the pointer flag set.
 #[repr(C)]
-pub(crate) struct FatPointer {
+pub struct FatPointer {
     pub offset: u32,
     pub memory_page: u32,
     pub start: u32,
@@ -1,38 +1,194 @@
-use crate::{addressing_modes::Arguments, vm::VirtualMachine, Predicate, World};
+use crate::{addressing_modes::Arguments, vm::VirtualMachine, Predicate, Tracer, World};
+use zkevm_opcode_defs::{
+    BinopOpcode, ContextOpcode, FarCallOpcode, JumpOpcode, LogOpcode, Opcode, PtrOpcode, RetOpcode,
+    ShiftOpcode, UMAOpcode,
+};
 
 #[derive(Hash, Debug)]
 pub struct Instruction {
     pub(crate) handler: Handler,
     pub(crate) arguments: Arguments,
+    /// Only used for introspection. Execution is entirely determined by the handler.
+    pub(crate) opcode: Opcode,
 }
 
-pub(crate) type Handler =
-    fn(&mut VirtualMachine, *const Instruction, &mut dyn World) -> InstructionResult;
+impl Instruction {
+    /// The opcode this instruction implements, ignoring addressing modes and flags.
+    pub fn opcode(&self) -> Opcode {
+        self.opcode
+    }
+
+    /// The name of the opcode in zkEVM assembly.
+    pub fn mnemonic(&self) -> &'static str {
+        match self.opcode {
+            Opcode::Invalid(_) => "invalid",
+            Opcode::Nop(_) => "nop",
+            Opcode::Add(_) => "add",
+            Opcode::Sub(_) => "sub",
+            Opcode::Mul(_) => "mul",
+            Opcode::Div(_) => "div",
+            Opcode::Jump(_) => "jump",
+            Opcode::Context(op) => match op {
+                ContextOpcode::This => "context.this",
+                ContextOpcode::Caller => "context.caller",
+                ContextOpcode::CodeAddress => "context.code_source",
+                ContextOpcode::Meta => "context.meta",
+                ContextOpcode::ErgsLeft => "context.ergs_left",
+                ContextOpcode::Sp => "context.sp",
+                ContextOpcode::GetContextU128 => "context.get_context_u128",
+                ContextOpcode::SetContextU128 => "context.set_context_u128",
+                ContextOpcode::AuxMutating0 => "context.aux_mutating0",
+                ContextOpcode::IncrementTxNumber => "context.inc_tx_num",
+            },
+            Opcode::Shift(op) => match op {
+                ShiftOpcode::Shl => "shl",
+                ShiftOpcode::Shr => "shr",
+                ShiftOpcode::Rol => "rol",
+                ShiftOpcode::Ror => "ror",
+            },
+            Opcode::Binop(op) => match op {
+                BinopOpcode::Xor => "xor",
+                BinopOpcode::And => "and",
+                BinopOpcode::Or => "or",
+            },
+            Opcode::Ptr(op) => match op {
+                PtrOpcode::Add => "ptr.add",
+                PtrOpcode::Sub => "ptr.sub",
+                PtrOpcode::Pack => "ptr.pack",
+                PtrOpcode::Shrink => "ptr.shrink",
+            },
+            Opcode::NearCall(_) => "near_call",
+            Opcode::Log(op) => match op {
+                LogOpcode::StorageRead => "log.sread",
+                LogOpcode::StorageWrite => "log.swrite",
+                LogOpcode::ToL1Message => "log.to_l1",
+                LogOpcode::Event => "log.event",
+                LogOpcode::PrecompileCall => "log.precompile",
+                LogOpcode::Decommit => "log.decommit",
+                LogOpcode::TransientStorageRead => "log.tread",
+                LogOpcode::TransientStorageWrite => "log.twrite",
+            },
+            Opcode::FarCall(op) => match op {
+                FarCallOpcode::Normal => "far_call",
+                FarCallOpcode::Delegate => "far_call.delegate",
+                FarCallOpcode::Mimic => "far_call.mimic",
+            },
+            Opcode::Ret(op) => match op {
+                RetOpcode::Ok => "ret",
+                RetOpcode::Revert => "ret.revert",
+                RetOpcode::Panic => "ret.panic",
+            },
+            Opcode::UMA(op) => match op {
+                UMAOpcode::HeapRead => "ld.1",
+                UMAOpcode::HeapWrite => "st.1",
+                UMAOpcode::AuxHeapRead => "ld.2",
+                UMAOpcode::AuxHeapWrite => "st.2",
+                UMAOpcode::FatPointerRead => "ld",
+                UMAOpcode::StaticMemoryRead => "ld.static",
+                UMAOpcode::StaticMemoryWrite => "st.static",
+            },
+        }
+    }
+}
+
+/// An instruction handler instantiated for the tracer that does nothing, whose calls compile away,
+/// and for all other tracers, which are called dynamically.
+#[derive(Hash, Debug)]
+pub(crate) struct Handler {
+    pub(crate) untraced:
+        fn(&mut VirtualMachine, *const Instruction, &mut dyn World, &mut ()) -> InstructionResult,
+    pub(crate) traced: fn(
+        &mut VirtualMachine,
+        *const Instruction,
+        &mut dyn World,
+        &mut dyn Tracer,
+    ) -> InstructionResult,
+}
 pub(crate) type InstructionResult = Result<*const Instruction, ExecutionEnd>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExecutionEnd {
     ProgramFinished(Vec<u8>),
     Reverted(Vec<u8>),
-    Panicked,
+    Panicked(PanicReason),
 
     /// Returned when the bootloader writes to the heap location [crate::Settings::hook_address]
+    /// and no handler is registered for the hook or the handler returns [crate::HookResult::Suspend].
     SuspendedOnHook {
         hook: u32,
         pc_to_resume_from: u16,
     },
+
+    /// Returned when the handler registered for `hook` returns [crate::HookResult::Abort].
+    /// Execution cannot be resumed.
+    AbortedByHook {
+        hook: u32,
+    },
+
+    /// Returned when [crate::Tracer::should_stop] asks to stop before executing an instruction.
+    StoppedByTracer {
+        pc_to_resume_from: u16,
+    },
+
+    /// Returned by [crate::VirtualMachine::resume_with_instruction_budget]
+    /// when the budget runs out before execution ends.
+    BudgetExhausted {
+        pc_to_resume_from: u16,
+    },
+
+    /// Returned when the flag passed to [crate::VirtualMachine::set_interrupt_flag] is set.
+    Interrupted {
+        pc_to_resume_from: u16,
+    },
+}
+
+/// Why a frame panicked.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PanicReason {
+    /// Gas ran out while paying for an instruction, memory growth or a precompile.
+    OutOfGas,
+    /// A static call attempted a write or another operation that isn't allowed in a static context.
+    StaticContextViolation,
+    /// A fat pointer was used where an integer is expected or vice versa.
+    IncorrectPointerTags,
+    /// Pointer arithmetic overflowed or moved the pointer out of its bounds.
+    PointerOverflow,
+    /// A heap access at an address so large that 32 bytes past it cannot be represented.
+    HeapOffsetTooLarge,
+    /// A far call couldn't create or forward the calldata pointer.
+    /// This includes running out of gas while growing the heap for the calldata.
+    InvalidCalldata,
+    /// A far return couldn't create or forward the return data pointer, or tried to return the calldata.
+    InvalidReturnData,
+    /// The called contract has no valid code or decommitting it would cost more gas than is available.
+    DecommitFailed,
+    /// A jump or return to a label that is not part of the program.
+    InvalidJump,
+    /// The invalid instruction was executed.
+    InvalidInstruction,
+    /// The program panicked deliberately using the panic instruction.
+    ExplicitPanic,
+    /// The decoder encountered an opcode this VM doesn't implement.
+    UnimplementedOpcode,
 }
 
 pub fn jump_to_beginning() -> Instruction {
     Instruction {
-        handler: jump_to_beginning_handler,
+        handler: Handler {
+            untraced: jump_to_beginning_handler,
+            traced: |vm, instruction, world, tracer| {
+                jump_to_beginning_handler(vm, instruction, world, tracer)
+            },
+        },
         arguments: Arguments::new(Predicate::Always, 0),
+        opcode: Opcode::Jump(JumpOpcode::Jump),
     }
 }
-fn jump_to_beginning_handler(
+fn jump_to_beginning_handler<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     _: *const Instruction,
     _: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     let first_instruction = &vm.state.current_frame.program.instructions()[0];
     Ok(first_instruction)
@@ -7,14 +7,25 @@ use crate::{
     },
     instruction::{Instruction, InstructionResult},
     predication::Flags,
-    VirtualMachine, World,
+    Tracer, VirtualMachine, World,
 };
 use u256::U256;
+use zkevm_opcode_defs::{
+    AddOpcode, BinopOpcode, DivOpcode, MulOpcode, Opcode, ShiftOpcode, SubOpcode,
+};
 
-fn binop<Op: Binop, In1: Source, Out: Destination, const SWAP: bool, const SET_FLAGS: bool>(
+fn binop<
+    T: Tracer + ?Sized,
+    Op: Binop,
+    In1: Source,
+    Out: Destination,
+    const SWAP: bool,
+    const SET_FLAGS: bool,
+>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, args, _| {
         let a = In1::get(args, &mut vm.state);
@@ -32,11 +43,13 @@ fn binop<Op: Binop, In1: Source, Out: Destination, const SWAP: bool, const SET_F
 
 pub trait Binop {
     type Out2: SecondOutput;
+    const OPCODE: Opcode;
     fn perform(a: &U256, b: &U256) -> (U256, Self::Out2, Flags);
 }
 
 pub struct Add;
 impl Binop for Add {
+    const OPCODE: Opcode = Opcode::Add(AddOpcode::Add);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let (result, overflow) = a.overflowing_add(*b);
@@ -51,6 +64,7 @@ impl Binop for Add {
 
 pub struct Sub;
 impl Binop for Sub {
+    const OPCODE: Opcode = Opcode::Sub(SubOpcode::Sub);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let (result, overflow) = a.overflowing_sub(*b);
@@ -65,6 +79,7 @@ impl Binop for Sub {
 
 pub struct And;
 impl Binop for And {
+    const OPCODE: Opcode = Opcode::Binop(BinopOpcode::And);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let result = *a & *b;
@@ -75,6 +90,7 @@ impl Binop for And {
 
 pub struct Or;
 impl Binop for Or {
+    const OPCODE: Opcode = Opcode::Binop(BinopOpcode::Or);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let result = *a | *b;
@@ -85,6 +101,7 @@ impl Binop for Or {
 
 pub struct Xor;
 impl Binop for Xor {
+    const OPCODE: Opcode = Opcode::Binop(BinopOpcode::Xor);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let result = *a ^ *b;
@@ -95,6 +112,7 @@ impl Binop for Xor {
 
 pub struct ShiftLeft;
 impl Binop for ShiftLeft {
+    const OPCODE: Opcode = Opcode::Shift(ShiftOpcode::Shl);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let result = *a << b.low_u32() as u8;
@@ -105,6 +123,7 @@ impl Binop for ShiftLeft {
 
 pub struct ShiftRight;
 impl Binop for ShiftRight {
+    const OPCODE: Opcode = Opcode::Shift(ShiftOpcode::Shr);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let result = *a >> b.low_u32() as u8;
@@ -115,6 +134,7 @@ impl Binop for ShiftRight {
 
 pub struct RotateLeft;
 impl Binop for RotateLeft {
+    const OPCODE: Opcode = Opcode::Shift(ShiftOpcode::Rol);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let shift = b.low_u32() as u8;
@@ -126,6 +146,7 @@ impl Binop for RotateLeft {
 
 pub struct RotateRight;
 impl Binop for RotateRight {
+    const OPCODE: Opcode = Opcode::Shift(ShiftOpcode::Ror);
     #[inline(always)]
     fn perform(a: &U256, b: &U256) -> (U256, (), Flags) {
         let shift = b.low_u32() as u8;
@@ -158,6 +179,7 @@ impl SecondOutput for U256 {
 
 pub struct Mul;
 impl Binop for Mul {
+    const OPCODE: Opcode = Opcode::Mul(MulOpcode::Mul);
     fn perform(a: &U256, b: &U256) -> (U256, Self::Out2, Flags) {
         let res = a.full_mul(*b);
         let (low_slice, high_slice) = res.0.split_at(4);
@@ -185,6 +207,7 @@ impl Binop for Mul {
 
 pub struct Div;
 impl Binop for Div {
+    const OPCODE: Opcode = Opcode::Div(DivOpcode::Div);
     fn perform(a: &U256, b: &U256) -> (U256, Self::Out2, Flags) {
         if *b != U256::zero() {
             let (quotient, remainder) = a.div_mod(*b);
@@ -220,6 +243,7 @@ impl Instruction {
                 .write_source(&src2)
                 .write_destination(&out)
                 .write_destination(&out2),
+            opcode: Op::OPCODE,
         }
     }
 }
@@ -7,15 +7,16 @@ use crate::{
     decommit::address_into_u256,
     instruction::InstructionResult,
     state::State,
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, Tracer, VirtualMachine, World,
 };
 use u256::U256;
-use zkevm_opcode_defs::VmMetaParameters;
+use zkevm_opcode_defs::{ContextOpcode, Opcode, VmMetaParameters};
 
-fn context<Op: ContextOp>(
+fn context<T: Tracer + ?Sized, Op: ContextOp>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, args, _| {
         let result = Op::get(&vm.state);
@@ -24,11 +25,13 @@ fn context<Op: ContextOp>(
 }
 
 trait ContextOp {
+    const OPCODE: ContextOpcode;
     fn get(state: &State) -> U256;
 }
 
 struct This;
 impl ContextOp for This {
+    const OPCODE: ContextOpcode = ContextOpcode::This;
     fn get(state: &State) -> U256 {
         address_into_u256(state.current_frame.address)
     }
@@ -36,6 +39,7 @@ impl ContextOp for This {
 
 struct Caller;
 impl ContextOp for Caller {
+    const OPCODE: ContextOpcode = ContextOpcode::Caller;
     fn get(state: &State) -> U256 {
         address_into_u256(state.current_frame.caller)
     }
@@ -43,6 +47,7 @@ impl ContextOp for Caller {
 
 struct CodeAddress;
 impl ContextOp for CodeAddress {
+    const OPCODE: ContextOpcode = ContextOpcode::CodeAddress;
     fn get(state: &State) -> U256 {
         address_into_u256(state.current_frame.code_address)
     }
@@ -50,6 +55,7 @@ impl ContextOp for CodeAddress {
 
 struct ErgsLeft;
 impl ContextOp for ErgsLeft {
+    const OPCODE: ContextOpcode = ContextOpcode::ErgsLeft;
     fn get(state: &State) -> U256 {
         U256([state.current_frame.gas as u64, 0, 0, 0])
     }
@@ -57,6 +63,7 @@ impl ContextOp for ErgsLeft {
 
 struct U128;
 impl ContextOp for U128 {
+    const OPCODE: ContextOpcode = ContextOpcode::GetContextU128;
     fn get(state: &State) -> U256 {
         state.get_context_u128().into()
     }
@@ -64,6 +71,7 @@ impl ContextOp for U128 {
 
 struct SP;
 impl ContextOp for SP {
+    const OPCODE: ContextOpcode = ContextOpcode::Sp;
     fn get(state: &State) -> U256 {
         state.current_frame.sp.into()
     }
@@ -71,6 +79,7 @@ impl ContextOp for SP {
 
 struct Meta;
 impl ContextOp for Meta {
+    const OPCODE: ContextOpcode = ContextOpcode::Meta;
     fn get(state: &State) -> U256 {
         VmMetaParameters {
             heap_size: state.heaps[state.current_frame.heap].len() as u32,
@@ -86,10 +95,11 @@ impl ContextOp for Meta {
     }
 }
 
-fn set_context_u128(
+fn set_context_u128<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(
         vm,
@@ -97,7 +107,13 @@ fn set_context_u128(
         world,
         |vm, args, world, continue_normally| {
             if vm.state.current_frame.is_static {
-                return free_panic(vm, world);
+                return free_panic(
+                    vm,
+                    instruction,
+                    PanicReason::StaticContextViolation,
+                    world,
+                    tracer,
+                );
             }
 
             let value = Register1::get(args, &mut vm.state).low_u128();
@@ -108,21 +124,25 @@ fn set_context_u128(
     )
 }
 
-fn increment_tx_number(
+fn increment_tx_number<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, _, _| {
         vm.start_new_tx();
     })
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     fn from_context<Op: ContextOp>(out: Register1, arguments: Arguments) -> Self {
         Self {
-            handler: context::<Op>,
+            handler: monomorphize!(context[Op]),
             arguments: arguments.write_destination(&out),
+            opcode: Opcode::Context(Op::OPCODE),
         }
     }
 
@@ -149,14 +169,16 @@ impl Instruction {
     }
     pub fn from_set_context_u128(src: Register1, arguments: Arguments) -> Self {
         Self {
-            handler: set_context_u128,
+            handler: monomorphize!(set_context_u128),
             arguments: arguments.write_source(&src),
+            opcode: Opcode::Context(ContextOpcode::SetContextU128),
         }
     }
     pub fn from_increment_tx_number(arguments: Arguments) -> Self {
         Self {
-            handler: increment_tx_number,
+            handler: monomorphize!(increment_tx_number),
             arguments,
+            opcode: Opcode::Context(ContextOpcode::IncrementTxNumber),
         }
     }
 }
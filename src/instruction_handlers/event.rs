@@ -3,15 +3,16 @@ use crate::{
     addressing_modes::{Arguments, Immediate1, Register1, Register2, Source},
     instruction::InstructionResult,
     modified_world::{Event, L2ToL1Log},
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, Tracer, VirtualMachine, World,
 };
 use u256::H160;
-use zkevm_opcode_defs::ADDRESS_EVENT_WRITER;
+use zkevm_opcode_defs::{LogOpcode, Opcode, ADDRESS_EVENT_WRITER};
 
-fn event(
+fn event<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(
         vm,
@@ -19,7 +20,13 @@ fn event(
         world,
         |vm, args, world, continue_normally| {
             if vm.state.current_frame.is_static {
-                return free_panic(vm, world);
+                return free_panic(
+                    vm,
+                    instruction,
+                    PanicReason::StaticContextViolation,
+                    world,
+                    tracer,
+                );
             }
             if vm.state.current_frame.address == H160::from_low_u64_be(ADDRESS_EVENT_WRITER as u64)
             {
@@ -27,13 +34,15 @@ fn event(
                 let value = Register2::get(args, &mut vm.state);
                 let is_first = Immediate1::get(args, &mut vm.state).low_u32() == 1;
 
-                vm.world_diff.record_event(Event {
+                let event = Event {
                     key,
                     value,
                     is_first,
                     shard_id: 0, // shards currently aren't supported
                     tx_number: vm.state.transaction_number,
-                });
+                };
+                tracer.on_event(vm, &event);
+                vm.world_diff.record_event(event);
             }
 
             continue_normally
@@ -41,10 +50,11 @@ fn event(
     )
 }
 
-fn l2_to_l1(
+fn l2_to_l1<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(
         vm,
@@ -52,26 +62,36 @@ fn l2_to_l1(
         world,
         |vm, args, world, continue_normally| {
             if vm.state.current_frame.is_static {
-                return free_panic(vm, world);
+                return free_panic(
+                    vm,
+                    instruction,
+                    PanicReason::StaticContextViolation,
+                    world,
+                    tracer,
+                );
             }
 
             let key = Register1::get(args, &mut vm.state);
             let value = Register2::get(args, &mut vm.state);
             let is_service = Immediate1::get(args, &mut vm.state).low_u32() == 1;
-            vm.world_diff.record_l2_to_l1_log(L2ToL1Log {
+            let log = L2ToL1Log {
                 key,
                 value,
                 is_service,
                 address: vm.state.current_frame.address,
                 shard_id: 0,
                 tx_number: vm.state.transaction_number,
-            });
+            };
+            tracer.on_l2_to_l1_log(vm, &log);
+            vm.world_diff.record_l2_to_l1_log(log);
 
             continue_normally
         },
     )
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     pub fn from_event(
         key: Register1,
@@ -80,11 +100,12 @@ impl Instruction {
         arguments: Arguments,
     ) -> Self {
         Self {
-            handler: event,
+            handler: monomorphize!(event),
             arguments: arguments
                 .write_source(&key)
                 .write_source(&value)
                 .write_source(&Immediate1(is_first.into())),
+            opcode: Opcode::Log(LogOpcode::Event),
         }
     }
 
@@ -95,11 +116,12 @@ impl Instruction {
         arguments: Arguments,
     ) -> Self {
         Self {
-            handler: l2_to_l1,
+            handler: monomorphize!(l2_to_l1),
             arguments: arguments
                 .write_source(&key)
                 .write_source(&value)
                 .write_source(&Immediate1(is_service.into())),
+            opcode: Opcode::Log(LogOpcode::ToL1Message),
         }
     }
 }
@@ -5,21 +5,33 @@ use crate::{
     fat_pointer::FatPointer,
     instruction::InstructionResult,
     predication::Flags,
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, Tracer, VirtualMachine, World,
 };
 use u256::U256;
 use zkevm_opcode_defs::{
     system_params::{EVM_SIMULATOR_STIPEND, MSG_VALUE_SIMULATOR_ADDITIVE_COST},
-    ADDRESS_MSG_VALUE,
+    FarCallOpcode, Opcode, ADDRESS_MSG_VALUE,
 };
 
 #[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum CallingMode {
     Normal,
     Delegate,
     Mimic,
 }
 
+impl CallingMode {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => CallingMode::Normal,
+            1 => CallingMode::Delegate,
+            2 => CallingMode::Mimic,
+            _ => unreachable!(),
+        }
+    }
+}
+
 /// A call to another contract.
 ///
 /// First, the code of the called contract is fetched and a fat pointer is created
@@ -30,10 +42,11 @@ pub enum CallingMode {
 ///
 /// Even though all errors happen before the new stack frame, they cause a panic in the new frame,
 /// not in the caller!
-fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
+fn far_call<T: Tracer + ?Sized, const CALLING_MODE: u8, const IS_STATIC: bool>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     let args = unsafe { &(*instruction).arguments };
 
@@ -48,6 +61,7 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
     let calldata =
         get_far_call_calldata(raw_abi, Register1::is_fat_pointer(args, &mut vm.state), vm);
 
+    let decommitted_before = vm.world_diff.get_decommitted_hashes().len();
     let decommit_result = vm.world_diff.decommit(
         world,
         destination_address,
@@ -56,6 +70,10 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
         &mut vm.state.current_frame.gas,
         abi.is_constructor_call,
     );
+    if let Some((_, code_hash, _)) = &decommit_result {
+        let is_fresh = vm.world_diff.get_decommitted_hashes().len() != decommitted_before;
+        tracer.on_decommit(vm, *code_hash, is_fresh);
+    }
 
     let mandated_gas = if destination_address == ADDRESS_MSG_VALUE.into() {
         MSG_VALUE_SIMULATOR_ADDITIVE_COST
@@ -67,7 +85,14 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
     if let Some(gas_left) = vm.state.current_frame.gas.checked_sub(mandated_gas) {
         vm.state.current_frame.gas = gas_left;
     } else {
-        return panic_from_failed_far_call(vm, exception_handler);
+        return failed_far_call::<_, CALLING_MODE>(
+            vm,
+            instruction,
+            destination_address,
+            0,
+            PanicReason::OutOfGas,
+            tracer,
+        );
     };
 
     let maximum_gas = vm.state.current_frame.gas / 64 * 63;
@@ -76,8 +101,25 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
 
     let new_frame_gas = new_frame_gas + mandated_gas;
 
-    let (Some(calldata), Some((program, is_evm_interpreter))) = (calldata, decommit_result) else {
-        return panic_from_failed_far_call(vm, exception_handler);
+    let Some(calldata) = calldata else {
+        return failed_far_call::<_, CALLING_MODE>(
+            vm,
+            instruction,
+            destination_address,
+            new_frame_gas,
+            PanicReason::InvalidCalldata,
+            tracer,
+        );
+    };
+    let Some((program, code_hash, is_evm_interpreter)) = decommit_result else {
+        return failed_far_call::<_, CALLING_MODE>(
+            vm,
+            instruction,
+            destination_address,
+            new_frame_gas,
+            PanicReason::DecommitFailed,
+            tracer,
+        );
     };
 
     let stipend = if is_evm_interpreter {
@@ -93,6 +135,7 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
         instruction,
         u256_into_address(destination_address),
         program,
+        code_hash,
         new_frame_gas,
         stipend,
         exception_handler,
@@ -123,9 +166,29 @@ fn far_call<const CALLING_MODE: u8, const IS_STATIC: bool>(
 
     vm.state.registers[2] = call_type.into();
 
+    tracer.on_far_call(vm, CallingMode::from_u8(CALLING_MODE));
+
     Ok(&vm.state.current_frame.program.instructions()[0])
 }
 
+fn failed_far_call<T: Tracer + ?Sized, const CALLING_MODE: u8>(
+    vm: &mut VirtualMachine,
+    instruction: *const Instruction,
+    destination_address: U256,
+    gas: u32,
+    reason: PanicReason,
+    tracer: &mut T,
+) -> InstructionResult {
+    tracer.on_failed_far_call(
+        vm,
+        CallingMode::from_u8(CALLING_MODE),
+        u256_into_address(destination_address),
+        gas,
+        reason,
+    );
+    panic_from_failed_far_call(vm, instruction, reason)
+}
+
 pub(crate) struct FarCallABI {
     pub gas_to_pass: u32,
     pub _shard_id: u8,
@@ -233,6 +296,11 @@ impl Instruction {
                 .write_source(&src1)
                 .write_source(&src2)
                 .write_source(&error_handler),
+            opcode: Opcode::FarCall(match CallingMode::from_u8(MODE) {
+                CallingMode::Normal => FarCallOpcode::Normal,
+                CallingMode::Delegate => FarCallOpcode::Delegate,
+                CallingMode::Mimic => FarCallOpcode::Mimic,
+            }),
         }
     }
 }
@@ -1,4 +1,4 @@
-use super::{common::instruction_boilerplate_with_panic, PANIC};
+use super::{common::instruction_boilerplate_with_panic, panic_with, ret::invalid_jump};
 use crate::{
     address_into_u256,
     addressing_modes::{
@@ -9,45 +9,59 @@ use crate::{
     fat_pointer::FatPointer,
     instruction::InstructionResult,
     state::State,
-    ExecutionEnd, Instruction, VirtualMachine, World,
+    ExecutionEnd, HookResult, Instruction, PanicReason, Tracer, VirtualMachine, World,
 };
 use u256::U256;
-use zkevm_opcode_defs::system_params::NEW_KERNEL_FRAME_MEMORY_STIPEND;
+use zkevm_opcode_defs::{system_params::NEW_KERNEL_FRAME_MEMORY_STIPEND, Opcode, UMAOpcode};
 
 pub trait HeapFromState {
+    const READ: UMAOpcode;
+    const WRITE: UMAOpcode;
     fn get_heap(state: &mut State) -> &mut Vec<u8>;
+    fn get_heap_id(state: &State) -> u32;
 }
 
 pub struct Heap;
 impl HeapFromState for Heap {
+    const READ: UMAOpcode = UMAOpcode::HeapRead;
+    const WRITE: UMAOpcode = UMAOpcode::HeapWrite;
     fn get_heap(state: &mut State) -> &mut Vec<u8> {
         &mut state.heaps[state.current_frame.heap]
     }
+    fn get_heap_id(state: &State) -> u32 {
+        state.current_frame.heap
+    }
 }
 
 pub struct AuxHeap;
 impl HeapFromState for AuxHeap {
+    const READ: UMAOpcode = UMAOpcode::AuxHeapRead;
+    const WRITE: UMAOpcode = UMAOpcode::AuxHeapWrite;
     fn get_heap(state: &mut State) -> &mut Vec<u8> {
         &mut state.heaps[state.current_frame.aux_heap]
     }
+    fn get_heap_id(state: &State) -> u32 {
+        state.current_frame.aux_heap
+    }
 }
 
 /// The last address to which 32 can be added without overflow.
 const LAST_ADDRESS: u32 = u32::MAX - 32;
 
-fn load<H: HeapFromState, In: Source, const INCREMENT: bool>(
+fn load<T: Tracer + ?Sized, H: HeapFromState, In: Source, const INCREMENT: bool>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
         let pointer = In::get(args, &mut vm.state);
         if In::is_fat_pointer(args, &mut vm.state) {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::IncorrectPointerTags);
         }
         if pointer > LAST_ADDRESS.into() {
             let _ = vm.state.use_gas(u32::MAX);
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::HeapOffsetTooLarge);
         }
 
         let address = pointer.low_u32();
@@ -56,12 +70,13 @@ fn load<H: HeapFromState, In: Source, const INCREMENT: bool>(
         let new_bound = address + 32;
 
         if grow_heap::<H>(&mut vm.state, new_bound).is_err() {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::OutOfGas);
         };
 
         let heap = H::get_heap(&mut vm.state);
         let value = U256::from_big_endian(&heap[address as usize..new_bound as usize]);
         Register1::set(args, &mut vm.state, value);
+        tracer.on_heap_read(vm, H::get_heap_id(&vm.state), address, value);
 
         if INCREMENT {
             Register2::set(args, &mut vm.state, pointer + 32)
@@ -71,51 +86,86 @@ fn load<H: HeapFromState, In: Source, const INCREMENT: bool>(
     })
 }
 
-fn store<H: HeapFromState, In: Source, const INCREMENT: bool, const HOOKING_ENABLED: bool>(
+fn store<
+    T: Tracer + ?Sized,
+    H: HeapFromState,
+    In: Source,
+    const INCREMENT: bool,
+    const HOOKING_ENABLED: bool,
+>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
-    instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
-        let pointer = In::get(args, &mut vm.state);
-        if In::is_fat_pointer(args, &mut vm.state) {
-            return Ok(&PANIC);
-        }
-        if pointer > LAST_ADDRESS.into() {
-            let _ = vm.state.use_gas(u32::MAX);
-            return Ok(&PANIC);
-        }
-        let address = pointer.low_u32();
+    let mut hook = None;
+    let result = instruction_boilerplate_with_panic(
+        vm,
+        instruction,
+        world,
+        |vm, args, _, continue_normally| {
+            let pointer = In::get(args, &mut vm.state);
+            if In::is_fat_pointer(args, &mut vm.state) {
+                return panic_with(vm, instruction, PanicReason::IncorrectPointerTags);
+            }
+            if pointer > LAST_ADDRESS.into() {
+                let _ = vm.state.use_gas(u32::MAX);
+                return panic_with(vm, instruction, PanicReason::HeapOffsetTooLarge);
+            }
+            let address = pointer.low_u32();
 
-        let value = Register2::get(args, &mut vm.state);
+            let value = Register2::get(args, &mut vm.state);
 
-        // The size check above ensures this never overflows
-        let new_bound = address + 32;
+            // The size check above ensures this never overflows
+            let new_bound = address + 32;
 
-        if grow_heap::<H>(&mut vm.state, new_bound).is_err() {
-            return Ok(&PANIC);
-        }
+            if grow_heap::<H>(&mut vm.state, new_bound).is_err() {
+                return panic_with(vm, instruction, PanicReason::OutOfGas);
+            }
 
-        let heap = H::get_heap(&mut vm.state);
-        value.to_big_endian(&mut heap[address as usize..new_bound as usize]);
+            let heap = H::get_heap(&mut vm.state);
+            value.to_big_endian(&mut heap[address as usize..new_bound as usize]);
+            tracer.on_heap_write(vm, H::get_heap_id(&vm.state), address, value);
 
-        if INCREMENT {
-            Register1::set(args, &mut vm.state, pointer + 32)
-        }
+            if INCREMENT {
+                Register1::set(args, &mut vm.state, pointer + 32)
+            }
 
-        if HOOKING_ENABLED && address == vm.settings.hook_address {
-            Err(ExecutionEnd::SuspendedOnHook {
-                hook: value.as_u32(),
-                pc_to_resume_from: vm
-                    .state
-                    .current_frame
-                    .pc_to_u16(instruction)
-                    .wrapping_add(1),
-            })
-        } else {
+            if HOOKING_ENABLED && address == vm.settings.hook_address {
+                hook = Some(value.as_u32());
+            }
             continue_normally
-        }
-    })
+        },
+    );
+
+    match hook {
+        Some(hook) => run_hook(vm, instruction, hook, world),
+        None => result,
+    }
+}
+
+/// The handler may change the VM arbitrarily, for example roll it back or replace the current frame,
+/// so nothing derived from `instruction` is used after it has run.
+/// Execution continues after the instruction with the same index in whatever frame is current then.
+fn run_hook(
+    vm: &mut VirtualMachine,
+    instruction: *const Instruction,
+    hook: u32,
+    world: &mut dyn World,
+) -> InstructionResult {
+    let pc = vm.state.current_frame.pc_to_u16(instruction);
+    let pc_to_resume_from = pc.wrapping_add(1);
+    match vm.handle_hook(hook, world) {
+        HookResult::Continue => match vm.state.current_frame.pc_from_u16(pc_to_resume_from) {
+            Some(next) => Ok(next),
+            None => invalid_jump(vm, pc),
+        },
+        HookResult::Suspend => Err(ExecutionEnd::SuspendedOnHook {
+            hook,
+            pc_to_resume_from,
+        }),
+        HookResult::Abort => Err(ExecutionEnd::AbortedByHook { hook }),
+    }
 }
 
 pub fn grow_heap<H: HeapFromState>(state: &mut State, new_bound: u32) -> Result<(), ()> {
@@ -133,14 +183,15 @@ pub fn grow_heap<H: HeapFromState>(state: &mut State, new_bound: u32) -> Result<
     Ok(())
 }
 
-fn load_pointer<const INCREMENT: bool>(
+fn load_pointer<T: Tracer + ?Sized, const INCREMENT: bool>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
         if !Register1::is_fat_pointer(args, &mut vm.state) {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::IncorrectPointerTags);
         }
         let input = Register1::get(args, &mut vm.state);
         let pointer = FatPointer::from(input);
@@ -151,7 +202,7 @@ fn load_pointer<const INCREMENT: bool>(
         // but if offset + 32 is not representable, we panic, even if we could've read some bytes.
         // This is not a bug, this is how it must work to be backwards compatible.
         if pointer.offset > LAST_ADDRESS {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::HeapOffsetTooLarge);
         };
 
         let mut buffer = [0; 32];
@@ -164,7 +215,14 @@ fn load_pointer<const INCREMENT: bool>(
             }
         }
 
-        Register1::set(args, &mut vm.state, U256::from_big_endian(&buffer));
+        let value = U256::from_big_endian(&buffer);
+        Register1::set(args, &mut vm.state, value);
+        tracer.on_heap_read(
+            vm,
+            pointer.memory_page,
+            pointer.start.wrapping_add(pointer.offset),
+            value,
+        );
 
         if INCREMENT {
             // This addition does not overflow because we checked that the offset is small enough above.
@@ -195,6 +253,7 @@ impl Instruction {
         Self {
             handler: monomorphize!(load [H] match_reg_imm src match_boolean increment),
             arguments,
+            opcode: Opcode::UMA(H::READ),
         }
     }
 
@@ -213,6 +272,7 @@ impl Instruction {
                 .write_source(&src1)
                 .write_source(&src2)
                 .write_destination(&incremented_out),
+            opcode: Opcode::UMA(H::WRITE),
         }
     }
 
@@ -230,6 +290,7 @@ impl Instruction {
                 .write_source(&src)
                 .write_destination(&out)
                 .write_destination(&incremented_out),
+            opcode: Opcode::UMA(UMAOpcode::FatPointerRead),
         }
     }
 }
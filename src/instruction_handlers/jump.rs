@@ -1,24 +1,26 @@
-use super::ret::INVALID_INSTRUCTION;
+use super::ret::invalid_jump;
 use crate::{
     addressing_modes::{
         AbsoluteStack, AdvanceStackPointer, AnySource, Arguments, CodePage, Immediate1, Register1,
         RelativeStack, Source,
     },
     instruction::{Instruction, InstructionResult},
-    VirtualMachine, World,
+    Tracer, VirtualMachine, World,
 };
+use zkevm_opcode_defs::{JumpOpcode, Opcode};
 
-fn jump<In: Source>(
+fn jump<T: Tracer + ?Sized, In: Source>(
     vm: &mut VirtualMachine,
     mut instruction: *const Instruction,
     _: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     unsafe {
         let target = In::get(&(*instruction).arguments, &mut vm.state).low_u32() as u16 as usize;
         if let Some(i) = vm.state.current_frame.program.instructions().get(target) {
             instruction = i;
         } else {
-            return Ok(&INVALID_INSTRUCTION);
+            return invalid_jump(vm, vm.state.current_frame.pc_to_u16(instruction));
         }
 
         Ok(instruction)
@@ -32,6 +34,7 @@ impl Instruction {
         Self {
             handler: monomorphize!(jump match_source source),
             arguments: arguments.write_source(&source),
+            opcode: Opcode::Jump(JumpOpcode::Jump),
         }
     }
 }
@@ -2,7 +2,8 @@ pub use binop::{Add, And, Div, Mul, Or, RotateLeft, RotateRight, ShiftLeft, Shif
 pub use far_call::CallingMode;
 pub use heap_access::{AuxHeap, Heap};
 pub use pointer::{PtrAdd, PtrPack, PtrShrink, PtrSub};
-pub(crate) use ret::{free_panic, PANIC};
+pub use ret::ReturnType;
+pub(crate) use ret::{free_panic, panic_with};
 
 mod binop;
 mod common;
@@ -5,6 +5,10 @@ macro_rules! monomorphize {
         $next_matcher!([$($types)*] $($rest)* parameterize $function_name)
     };
 
+    ($function_name: ident [$($types: tt)*]) => {
+        parameterize!([$($types)*] $function_name)
+    };
+
     ($function_name: ident $($rest: ident)*) => {
         monomorphize!($function_name [] $($rest)*)
     };
@@ -53,9 +57,16 @@ macro_rules! match_boolean {
     };
 }
 
+/// Instantiates the function for the tracer that does nothing and for all other tracers.
+/// The latter goes through a closure because the tracer's lifetime isn't known here.
 macro_rules! parameterize {
     ([$($types: tt)*] $function_name:ident) => {
-        $function_name::<$($types),*>
+        $crate::instruction::Handler {
+            untraced: $function_name::<(), $($types),*>,
+            traced: |vm, instruction, world, tracer| {
+                $function_name::<_, $($types),*>(vm, instruction, world, tracer)
+            },
+        }
     };
 }
 
@@ -2,18 +2,20 @@ use crate::{
     addressing_modes::{Arguments, Immediate1, Immediate2, Register1, Source},
     instruction::InstructionResult,
     predication::Flags,
-    Instruction, VirtualMachine, World,
+    Instruction, Tracer, VirtualMachine, World,
 };
+use zkevm_opcode_defs::{NearCallOpcode, Opcode};
 
-fn near_call(
+fn near_call<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     _: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     let args = unsafe { &(*instruction).arguments };
 
     let gas_to_pass = Register1::get(args, &mut vm.state).0[0] as u32;
-    let destination = Immediate1::get(args, &mut vm.state);
+    let destination = Immediate1::get(args, &mut vm.state).low_u32() as u16;
     let error_handler = Immediate2::get(args, &mut vm.state);
 
     let new_frame_gas = if gas_to_pass == 0 {
@@ -30,9 +32,13 @@ fn near_call(
 
     vm.state.flags = Flags::new(false, false, false);
 
-    Ok(&vm.state.current_frame.program.instructions()[destination.low_u32() as usize])
+    tracer.on_near_call(vm, destination);
+
+    Ok(&vm.state.current_frame.program.instructions()[destination as usize])
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     pub fn from_near_call(
         gas: Register1,
@@ -41,11 +47,12 @@ impl Instruction {
         arguments: Arguments,
     ) -> Self {
         Self {
-            handler: near_call,
+            handler: monomorphize!(near_call),
             arguments: arguments
                 .write_source(&gas)
                 .write_source(&destination)
                 .write_source(&error_handler),
+            opcode: Opcode::NearCall(NearCallOpcode::NearCall),
         }
     }
 }
@@ -2,13 +2,15 @@ use super::common::instruction_boilerplate;
 use crate::{
     addressing_modes::{destination_stack_address, AdvanceStackPointer, Arguments, Source},
     instruction::InstructionResult,
-    Instruction, VirtualMachine, World,
+    Instruction, Tracer, VirtualMachine, World,
 };
+use zkevm_opcode_defs::{NopOpcode, Opcode};
 
-fn nop(
+fn nop<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, args, _| {
         // nop's addressing modes can move the stack pointer!
@@ -21,6 +23,8 @@ fn nop(
     })
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     pub fn from_nop(
         pop: AdvanceStackPointer,
@@ -28,8 +32,9 @@ impl Instruction {
         arguments: Arguments,
     ) -> Self {
         Self {
-            handler: nop,
+            handler: monomorphize!(nop),
             arguments: arguments.write_source(&pop).write_destination(&push),
+            opcode: Opcode::Nop(NopOpcode::Nop),
         }
     }
 }
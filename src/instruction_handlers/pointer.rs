@@ -1,4 +1,4 @@
-use super::{common::instruction_boilerplate_with_panic, PANIC};
+use super::{common::instruction_boilerplate_with_panic, panic_with};
 use crate::{
     addressing_modes::{
         AbsoluteStack, AdvanceStackPointer, AnyDestination, AnySource, Arguments, CodePage,
@@ -6,14 +6,16 @@ use crate::{
     },
     fat_pointer::FatPointer,
     instruction::InstructionResult,
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, Tracer, VirtualMachine, World,
 };
 use u256::U256;
+use zkevm_opcode_defs::{Opcode, PtrOpcode};
 
-fn ptr<Op: PtrOp, In1: Source, Out: Destination, const SWAP: bool>(
+fn ptr<T: Tracer + ?Sized, Op: PtrOp, In1: Source, Out: Destination, const SWAP: bool>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    _: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
         let a = (
@@ -29,11 +31,11 @@ fn ptr<Op: PtrOp, In1: Source, Out: Destination, const SWAP: bool>(
         let (b, b_is_pointer) = b;
 
         if !a_is_pointer || b_is_pointer {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::IncorrectPointerTags);
         }
 
         let Some(result) = Op::perform(a, b) else {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::PointerOverflow);
         };
 
         Out::set_fat_ptr(args, &mut vm.state, result);
@@ -43,6 +45,7 @@ fn ptr<Op: PtrOp, In1: Source, Out: Destination, const SWAP: bool>(
 }
 
 pub trait PtrOp {
+    const OPCODE: PtrOpcode;
     fn perform(in1: U256, in2: U256) -> Option<U256>;
 }
 
@@ -51,6 +54,11 @@ pub type PtrAdd = PtrAddSub<true>;
 pub type PtrSub = PtrAddSub<false>;
 
 impl<const IS_ADD: bool> PtrOp for PtrAddSub<IS_ADD> {
+    const OPCODE: PtrOpcode = if IS_ADD {
+        PtrOpcode::Add
+    } else {
+        PtrOpcode::Sub
+    };
     fn perform(mut in1: U256, in2: U256) -> Option<U256> {
         if in2 > u32::MAX.into() {
             return None;
@@ -71,6 +79,7 @@ impl<const IS_ADD: bool> PtrOp for PtrAddSub<IS_ADD> {
 
 pub struct PtrPack;
 impl PtrOp for PtrPack {
+    const OPCODE: PtrOpcode = PtrOpcode::Pack;
     fn perform(in1: U256, in2: U256) -> Option<U256> {
         if in2.low_u128() != 0 {
             None
@@ -82,6 +91,7 @@ impl PtrOp for PtrPack {
 
 pub struct PtrShrink;
 impl PtrOp for PtrShrink {
+    const OPCODE: PtrOpcode = PtrOpcode::Shrink;
     fn perform(mut in1: U256, in2: U256) -> Option<U256> {
         let pointer: &mut FatPointer = (&mut in1).into();
         pointer.length = pointer.length.checked_sub(in2.low_u32())?;
@@ -106,6 +116,7 @@ impl Instruction {
                 .write_source(&src1)
                 .write_source(&src2)
                 .write_destination(&out),
+            opcode: Opcode::Ptr(Op::OPCODE),
         }
     }
 }
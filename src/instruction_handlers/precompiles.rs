@@ -1,18 +1,17 @@
-use super::{common::instruction_boilerplate_with_panic, PANIC};
+use super::{common::instruction_boilerplate_with_panic, panic_with};
 use crate::{
     addressing_modes::{Arguments, Destination, Register1, Register2, Source},
     instruction::InstructionResult,
     state::Heaps,
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, PrecompileCall, Tracer, VirtualMachine, World,
 };
 use u256::U256;
 use zk_evm_abstractions::{
-    aux::Timestamp,
     precompiles::{
         ecrecover::ecrecover_function, keccak256::keccak256_rounds_function,
         secp256r1_verify::secp256r1_verify_function, sha256::sha256_rounds_function,
     },
-    queries::LogQuery,
+    queries::{LogQuery, MemoryQuery},
     vm::Memory,
 };
 use zkevm_opcode_defs::{
@@ -20,13 +19,14 @@ use zkevm_opcode_defs::{
         ECRECOVER_INNER_FUNCTION_PRECOMPILE_ADDRESS, KECCAK256_ROUND_FUNCTION_PRECOMPILE_ADDRESS,
         SECP256R1_VERIFY_PRECOMPILE_ADDRESS, SHA256_ROUND_FUNCTION_PRECOMPILE_ADDRESS,
     },
-    PrecompileAuxData, PrecompileCallABI,
+    LogOpcode, Opcode, PrecompileAuxData, PrecompileCallABI, PRECOMPILE_AUX_BYTE,
 };
 
-fn precompile_call(
+fn precompile_call<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
         // TODO check that we're in a system call
@@ -35,7 +35,7 @@ fn precompile_call(
         // This is safe because system contracts are trusted
         let aux_data = PrecompileAuxData::from_u256(Register2::get(args, &mut vm.state));
         let Ok(()) = vm.state.use_gas(aux_data.extra_ergs_cost) else {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::OutOfGas);
         };
 
         // TODO record extra pubdata cost
@@ -48,13 +48,14 @@ fn precompile_call(
             abi.memory_page_to_write = vm.state.current_frame.heap;
         }
 
+        let address = vm.state.current_frame.address;
         let query = LogQuery {
-            timestamp: Timestamp(0),
+            timestamp: vm.state.timestamp_for_first_decommit_or_precompile_read(),
             key: abi.to_u256(),
-            tx_number_in_block: Default::default(),
-            aux_byte: Default::default(),
+            tx_number_in_block: vm.state.transaction_number,
+            aux_byte: PRECOMPILE_AUX_BYTE,
             shard_id: Default::default(),
-            address: Default::default(),
+            address,
             read_value: Default::default(),
             written_value: Default::default(),
             rw_flag: Default::default(),
@@ -62,56 +63,104 @@ fn precompile_call(
             is_service: Default::default(),
         };
 
-        let address_bytes = vm.state.current_frame.address.0;
-        let address_low = u16::from_le_bytes([address_bytes[19], address_bytes[18]]);
-        let heaps = &mut vm.state.heaps;
-        match address_low {
+        let address_low = u16::from_le_bytes([address.0[19], address.0[18]]);
+        let mut memory = PrecompileMemory {
+            heaps: &mut vm.state.heaps,
+            record_reads: tracer.wants_precompile_memory_queries(),
+            reads: vec![],
+            writes: vec![],
+        };
+        let cycle = vm.state.monotonic_cycle_counter;
+        let rounds = match address_low {
             KECCAK256_ROUND_FUNCTION_PRECOMPILE_ADDRESS => {
-                keccak256_rounds_function::<_, false>(0, query, heaps);
+                keccak256_rounds_function::<_, false>(cycle, query, &mut memory).0
             }
             SHA256_ROUND_FUNCTION_PRECOMPILE_ADDRESS => {
-                sha256_rounds_function::<_, false>(0, query, heaps);
+                sha256_rounds_function::<_, false>(cycle, query, &mut memory).0
             }
             ECRECOVER_INNER_FUNCTION_PRECOMPILE_ADDRESS => {
-                ecrecover_function::<_, false>(0, query, heaps);
+                ecrecover_function::<_, false>(cycle, query, &mut memory).0
             }
             SECP256R1_VERIFY_PRECOMPILE_ADDRESS => {
-                secp256r1_verify_function::<_, false>(0, query, heaps);
+                secp256r1_verify_function::<_, false>(cycle, query, &mut memory).0
             }
             _ => {
                 // A precompile call may be used just to burn gas
+                0
             }
+        };
+        let PrecompileMemory {
+            record_reads,
+            reads: memory_reads,
+            writes,
+            ..
+        } = memory;
+
+        for (_, write) in &writes {
+            tracer.on_heap_write(
+                vm,
+                write.location.page.0,
+                write.location.index.0 * 32,
+                write.value,
+            );
         }
+        let memory_writes = if record_reads { writes } else { vec![] };
 
         Register1::set(args, &mut vm.state, 1.into());
+        tracer.on_precompile_call(
+            vm,
+            &PrecompileCall {
+                address,
+                query,
+                rounds,
+                memory_reads,
+                memory_writes,
+            },
+        );
 
         continue_normally
     })
 }
 
-impl Memory for Heaps {
+/// The heaps as seen by a precompile. Remembers the queries made to them
+/// along with the [crate::State::monotonic_cycle_counter] they were made in,
+/// so that they can be reported to the tracer.
+struct PrecompileMemory<'a> {
+    heaps: &'a mut Heaps,
+    /// Writes are always recorded, as they are reported through [Tracer::on_heap_write].
+    record_reads: bool,
+    reads: Vec<(u32, MemoryQuery)>,
+    writes: Vec<(u32, MemoryQuery)>,
+}
+
+impl Memory for PrecompileMemory<'_> {
     fn execute_partial_query(
         &mut self,
-        _monotonic_cycle_counter: u32,
-        mut query: zk_evm_abstractions::queries::MemoryQuery,
-    ) -> zk_evm_abstractions::queries::MemoryQuery {
+        monotonic_cycle_counter: u32,
+        mut query: MemoryQuery,
+    ) -> MemoryQuery {
         let page = query.location.page.0;
         let start = query.location.index.0 as usize * 32;
         let range = start..start + 32;
         if query.rw_flag {
-            if range.end > self[page].len() {
-                self[page].resize(range.end, 0);
+            let heap = &mut self.heaps[page];
+            if range.end > heap.len() {
+                heap.resize(range.end, 0);
             }
-            query.value.to_big_endian(&mut self[page][range]);
+            query.value.to_big_endian(&mut heap[range]);
+            self.writes.push((monotonic_cycle_counter, query));
         } else {
             let mut buffer = [0; 32];
             for (i, page_index) in range.enumerate() {
-                if let Some(byte) = self[page].get(page_index) {
+                if let Some(byte) = self.heaps[page].get(page_index) {
                     buffer[i] = *byte;
                 }
             }
             query.value = U256::from_big_endian(&buffer);
             query.value_is_pointer = false;
+            if self.record_reads {
+                self.reads.push((monotonic_cycle_counter, query));
+            }
         }
         query
     }
@@ -119,20 +168,18 @@ impl Memory for Heaps {
     fn specialized_code_query(
         &mut self,
         _monotonic_cycle_counter: u32,
-        _query: zk_evm_abstractions::queries::MemoryQuery,
-    ) -> zk_evm_abstractions::queries::MemoryQuery {
+        _query: MemoryQuery,
+    ) -> MemoryQuery {
         todo!()
     }
 
-    fn read_code_query(
-        &self,
-        _monotonic_cycle_counter: u32,
-        _query: zk_evm_abstractions::queries::MemoryQuery,
-    ) -> zk_evm_abstractions::queries::MemoryQuery {
+    fn read_code_query(&self, _monotonic_cycle_counter: u32, _query: MemoryQuery) -> MemoryQuery {
         todo!()
     }
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     pub fn from_precompile_call(
         abi: Register1,
@@ -145,7 +192,8 @@ impl Instruction {
                 .write_source(&abi)
                 .write_source(&burn)
                 .write_destination(&out),
-            handler: precompile_call,
+            handler: monomorphize!(precompile_call),
+            opcode: Opcode::Log(LogOpcode::PrecompileCall),
         }
     }
 }
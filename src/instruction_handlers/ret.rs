@@ -4,20 +4,21 @@ use crate::{
     callframe::FrameRemnant,
     instruction::{ExecutionEnd, InstructionResult},
     predication::Flags,
-    Instruction, Predicate, VirtualMachine, World,
+    Instruction, PanicReason, Predicate, Tracer, VirtualMachine, World,
 };
 use u256::U256;
+use zkevm_opcode_defs::{InvalidOpcode, Opcode, RetOpcode};
 
 #[repr(u8)]
-#[derive(PartialEq)]
-enum ReturnType {
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReturnType {
     Normal = 0,
     Revert,
     Panic,
 }
 
 impl ReturnType {
-    fn is_failure(&self) -> bool {
+    pub fn is_failure(&self) -> bool {
         *self != ReturnType::Normal
     }
 
@@ -31,108 +32,149 @@ impl ReturnType {
     }
 }
 
-fn ret<const RETURN_TYPE: u8, const TO_LABEL: bool>(
+fn ret<T: Tracer + ?Sized, const RETURN_TYPE: u8, const TO_LABEL: bool>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     _: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     let args = unsafe { &(*instruction).arguments };
 
     let mut return_type = ReturnType::from_u8(RETURN_TYPE);
+    let (mut panic_reason, failing_pc) = match vm.pending_panic.take() {
+        Some((reason, pc)) => (Some(reason), pc),
+        None => (
+            (return_type == ReturnType::Panic).then_some(PanicReason::ExplicitPanic),
+            vm.state.current_frame.pc_to_u16(instruction),
+        ),
+    };
     let near_call_leftover_gas = vm.state.current_frame.gas;
 
-    let (pc, snapshot, leftover_gas, total_pubdata_spent) = if let Some(FrameRemnant {
-        program_counter,
-        exception_handler,
-        snapshot,
-        total_pubdata_spent,
-    }) =
-        vm.state.current_frame.pop_near_call()
-    {
-        (
-            if TO_LABEL {
-                Immediate1::get(args, &mut vm.state).low_u32() as u16
-            } else if return_type.is_failure() {
-                exception_handler
-            } else {
-                program_counter.wrapping_add(1)
-            },
-            snapshot,
-            near_call_leftover_gas,
-            total_pubdata_spent,
-        )
-    } else {
-        let return_value_or_panic = if return_type == ReturnType::Panic {
-            None
+    if vm.state.current_frame.is_in_near_call() {
+        if return_type.is_failure() {
+            vm.record_failure(failing_pc);
         } else {
-            let result = get_far_call_calldata(
-                Register1::get(args, &mut vm.state),
-                Register1::is_fat_pointer(args, &mut vm.state),
-                vm,
-            )
-            .filter(|pointer| pointer.memory_page != vm.state.current_frame.calldata_heap);
-
-            if result.is_none() {
-                return_type = ReturnType::Panic;
-            }
-            result
-        };
-
-        let leftover_gas = vm
-            .state
-            .current_frame
-            .gas
-            .saturating_sub(vm.state.current_frame.stipend);
+            vm.record_success();
+        }
+        if let Some(reason) = panic_reason {
+            tracer.on_panic(vm, reason);
+        }
+        tracer.on_near_return(vm, return_type);
+    }
 
-        let Some(FrameRemnant {
+    let (pc, call_instruction, snapshot, leftover_gas, total_pubdata_spent) =
+        if let Some(FrameRemnant {
             program_counter,
             exception_handler,
             snapshot,
             total_pubdata_spent,
-        }) = vm.pop_frame(
-            return_value_or_panic
-                .as_ref()
-                .map(|pointer| pointer.memory_page),
-        )
-        else {
-            // The initial frame is not rolled back, even if it fails.
-            // It is the caller's job to clean up when the execution as a whole fails because
-            // the caller may take external snapshots while the VM is in the initial frame and
-            // these would break were the initial frame to be rolled back.
-
-            return if let Some(return_value) = return_value_or_panic {
-                let output = vm.state.heaps[return_value.memory_page][return_value.start as usize
-                    ..(return_value.start + return_value.length) as usize]
-                    .to_vec();
-                if return_type == ReturnType::Revert {
-                    Err(ExecutionEnd::Reverted(output))
+        }) = vm.state.current_frame.pop_near_call()
+        {
+            (
+                if TO_LABEL {
+                    Immediate1::get(args, &mut vm.state).low_u32() as u16
+                } else if return_type.is_failure() {
+                    exception_handler
                 } else {
-                    Err(ExecutionEnd::ProgramFinished(output))
+                    program_counter.wrapping_add(1)
+                },
+                program_counter,
+                snapshot,
+                near_call_leftover_gas,
+                total_pubdata_spent,
+            )
+        } else {
+            let return_value_or_panic = if return_type == ReturnType::Panic {
+                None
+            } else {
+                let result = get_far_call_calldata(
+                    Register1::get(args, &mut vm.state),
+                    Register1::is_fat_pointer(args, &mut vm.state),
+                    vm,
+                )
+                .filter(|pointer| pointer.memory_page != vm.state.current_frame.calldata_heap);
+
+                if result.is_none() {
+                    return_type = ReturnType::Panic;
+                    panic_reason = Some(PanicReason::InvalidReturnData);
                 }
+                result
+            };
+
+            if return_type.is_failure() {
+                vm.record_failure(failing_pc);
             } else {
-                Err(ExecutionEnd::Panicked)
+                vm.record_success();
+            }
+            if let Some(reason) = panic_reason {
+                tracer.on_panic(vm, reason);
+            }
+
+            let return_data = return_value_or_panic.as_ref().map_or(&[][..], |pointer| {
+                &vm.state.heaps[pointer.memory_page]
+                    [pointer.start as usize..(pointer.start + pointer.length) as usize]
+            });
+            tracer.on_far_return(vm, return_type, return_data);
+
+            let leftover_gas = vm
+                .state
+                .current_frame
+                .gas
+                .saturating_sub(vm.state.current_frame.stipend);
+
+            let Some(FrameRemnant {
+                program_counter,
+                exception_handler,
+                snapshot,
+                total_pubdata_spent,
+            }) = vm.pop_frame(
+                return_value_or_panic
+                    .as_ref()
+                    .map(|pointer| pointer.memory_page),
+            )
+            else {
+                // The initial frame is not rolled back, even if it fails.
+                // It is the caller's job to clean up when the execution as a whole fails because
+                // the caller may take external snapshots while the VM is in the initial frame and
+                // these would break were the initial frame to be rolled back.
+
+                return if let Some(return_value) = return_value_or_panic {
+                    let output = vm.state.heaps[return_value.memory_page][return_value.start
+                        as usize
+                        ..(return_value.start + return_value.length) as usize]
+                        .to_vec();
+                    if return_type == ReturnType::Revert {
+                        Err(ExecutionEnd::Reverted(output))
+                    } else {
+                        Err(ExecutionEnd::ProgramFinished(output))
+                    }
+                } else {
+                    Err(ExecutionEnd::Panicked(
+                        panic_reason.expect("a frame without return value must have panicked"),
+                    ))
+                };
             };
-        };
 
-        vm.state.set_context_u128(0);
-        vm.state.registers = [U256::zero(); 16];
+            vm.state.set_context_u128(0);
+            vm.state.registers = [U256::zero(); 16];
 
-        if let Some(return_value) = return_value_or_panic {
-            vm.state.registers[1] = return_value.into_u256();
-        }
-        vm.state.register_pointer_flags = 2;
+            if let Some(return_value) = return_value_or_panic {
+                vm.state.registers[1] = return_value.into_u256();
+            }
+            vm.state.register_pointer_flags = 2;
 
-        (
-            if return_type.is_failure() {
-                exception_handler
-            } else {
-                program_counter.wrapping_add(1)
-            },
-            snapshot,
-            leftover_gas,
-            total_pubdata_spent,
-        )
-    };
+            (
+                if return_type.is_failure() {
+                    exception_handler
+                } else {
+                    program_counter.wrapping_add(1)
+                },
+                program_counter,
+                snapshot,
+                leftover_gas,
+                total_pubdata_spent,
+            )
+        };
 
     if return_type.is_failure() {
         vm.world_diff.rollback(snapshot);
@@ -145,17 +187,41 @@ fn ret<const RETURN_TYPE: u8, const TO_LABEL: bool>(
 
     match vm.state.current_frame.pc_from_u16(pc) {
         Some(i) => Ok(i),
-        None => Ok(&INVALID_INSTRUCTION),
+        None => invalid_jump(vm, call_instruction),
     }
 }
 
 /// Formally, a far call pushes a new frame and returns from it immediately if it panics.
-/// This function instead panics without popping a frame to save on allocation.
-/// TODO: when tracers are implemented, this function should count as a separate instruction!
+/// Instead, the far call continues at [FAR_CALL_PANIC], which panics without pushing or popping a frame
+/// to save on allocation. Like the return from the new frame would, the panic takes a cycle of its own.
 pub(crate) fn panic_from_failed_far_call(
     vm: &mut VirtualMachine,
-    exception_handler: u16,
+    instruction: *const Instruction,
+    reason: PanicReason,
 ) -> InstructionResult {
+    vm.pending_panic = Some((reason, vm.state.current_frame.pc_to_u16(instruction)));
+    Ok(&FAR_CALL_PANIC)
+}
+
+const FAR_CALL_PANIC: Instruction = Instruction {
+    handler: monomorphize!(far_call_panic),
+    arguments: Arguments::new(Predicate::Always, 0),
+    opcode: Opcode::Ret(RetOpcode::Panic),
+};
+
+fn far_call_panic<T: Tracer + ?Sized>(
+    vm: &mut VirtualMachine,
+    _: *const Instruction,
+    _: &mut dyn World,
+    tracer: &mut T,
+) -> InstructionResult {
+    let (reason, pc) = vm
+        .pending_panic
+        .take()
+        .expect("only reached after a failed far call");
+    vm.record_failure(pc);
+    tracer.on_panic(vm, reason);
+
     // Gas is already subtracted in the far call code.
     // No need to roll back, as no changes are made in this "frame".
 
@@ -166,24 +232,64 @@ pub(crate) fn panic_from_failed_far_call(
 
     vm.state.flags = Flags::new(true, false, false);
 
+    let far_call: *const Instruction = &vm.state.current_frame.program.instructions()[pc as usize];
+    let exception_handler =
+        Immediate1::get(unsafe { &(*far_call).arguments }, &mut vm.state).low_u32() as u16;
     match vm.state.current_frame.pc_from_u16(exception_handler) {
         Some(i) => Ok(i),
-        None => Ok(&INVALID_INSTRUCTION),
+        None => invalid_jump(vm, pc),
     }
 }
 
 /// Panics, burning all available gas.
 pub const INVALID_INSTRUCTION: Instruction = Instruction {
-    handler: ret::<{ ReturnType::Panic as u8 }, false>,
+    handler: monomorphize!(invalid),
     arguments: Arguments::new(Predicate::Always, INVALID_INSTRUCTION_COST),
+    opcode: Opcode::Invalid(InvalidOpcode::Invalid),
 };
 
+/// Only reached if the frame has exactly enough gas to pay for [INVALID_INSTRUCTION].
+fn invalid<T: Tracer + ?Sized>(
+    vm: &mut VirtualMachine,
+    instruction: *const Instruction,
+    world: &mut dyn World,
+    tracer: &mut T,
+) -> InstructionResult {
+    // Reached via [invalid_jump], `instruction` isn't part of the program,
+    // but then the reason has already been recorded.
+    if vm.pending_panic.is_none() {
+        vm.pending_panic = Some((
+            PanicReason::InvalidInstruction,
+            vm.state.current_frame.pc_to_u16(instruction),
+        ));
+    }
+    ret::<_, { ReturnType::Panic as u8 }, false>(vm, instruction, world, tracer)
+}
+
+/// Continues execution at [INVALID_INSTRUCTION], remembering that it was reached
+/// via a bad jump from the instruction at `pc`.
+pub(crate) fn invalid_jump(vm: &mut VirtualMachine, pc: u16) -> InstructionResult {
+    vm.pending_panic = Some((PanicReason::InvalidJump, pc));
+    Ok(&INVALID_INSTRUCTION)
+}
+
 const RETURN_COST: u32 = 5;
-pub const PANIC: Instruction = Instruction {
-    handler: ret::<{ ReturnType::Panic as u8 }, false>,
+const PANIC: Instruction = Instruction {
+    handler: monomorphize!(ret [{ ReturnType::Panic as u8 } false]),
     arguments: Arguments::new(Predicate::Always, RETURN_COST),
+    opcode: Opcode::Ret(RetOpcode::Panic),
 };
 
+/// Continues execution at [PANIC], which will panic for the given reason.
+pub(crate) fn panic_with(
+    vm: &mut VirtualMachine,
+    instruction: *const Instruction,
+    reason: PanicReason,
+) -> InstructionResult {
+    vm.pending_panic = Some((reason, vm.state.current_frame.pc_to_u16(instruction)));
+    Ok(&PANIC)
+}
+
 /// Turn the current instruction into a panic at no extra cost. (Great value, I know.)
 ///
 /// Call this when:
@@ -192,9 +298,20 @@ pub const PANIC: Instruction = Instruction {
 /// - using privileged instructions while not in a system call
 /// - the far call stack overflows
 ///
-/// For all other panics, point the instruction pointer at [PANIC] instead.
-pub(crate) fn free_panic(vm: &mut VirtualMachine, world: &mut dyn World) -> InstructionResult {
-    ret::<{ ReturnType::Panic as u8 }, false>(vm, &PANIC, world)
+/// For all other panics, use [panic_with] instead.
+///
+/// A reason that was already recorded, for example by [invalid_jump], takes precedence over `reason`.
+pub(crate) fn free_panic<T: Tracer + ?Sized>(
+    vm: &mut VirtualMachine,
+    instruction: *const Instruction,
+    reason: PanicReason,
+    world: &mut dyn World,
+    tracer: &mut T,
+) -> InstructionResult {
+    if vm.pending_panic.is_none() {
+        vm.pending_panic = Some((reason, vm.state.current_frame.pc_to_u16(instruction)));
+    }
+    ret::<_, { ReturnType::Panic as u8 }, false>(vm, &PANIC, world, tracer)
 }
 
 use super::monomorphization::*;
@@ -206,6 +323,7 @@ impl Instruction {
         Self {
             handler: monomorphize!(ret [RETURN_TYPE] match_boolean to_label),
             arguments: arguments.write_source(&src1).write_source(&label),
+            opcode: Opcode::Ret(RetOpcode::Ok),
         }
     }
     pub fn from_revert(src1: Register1, label: Option<Immediate1>, arguments: Arguments) -> Self {
@@ -214,6 +332,7 @@ impl Instruction {
         Self {
             handler: monomorphize!(ret [RETURN_TYPE] match_boolean to_label),
             arguments: arguments.write_source(&src1).write_source(&label),
+            opcode: Opcode::Ret(RetOpcode::Revert),
         }
     }
     pub fn from_panic(label: Option<Immediate1>, arguments: Arguments) -> Self {
@@ -222,6 +341,7 @@ impl Instruction {
         Self {
             handler: monomorphize!(ret [RETURN_TYPE] match_boolean to_label),
             arguments: arguments.write_source(&label),
+            opcode: Opcode::Ret(RetOpcode::Panic),
         }
     }
 
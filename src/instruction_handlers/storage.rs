@@ -1,19 +1,21 @@
 use super::{
     common::{instruction_boilerplate, instruction_boilerplate_with_panic},
-    PANIC,
+    panic_with,
 };
 use crate::{
     addressing_modes::{
         Arguments, Destination, Register1, Register2, Source, SLOAD_COST, SSTORE_COST,
     },
     instruction::InstructionResult,
-    Instruction, VirtualMachine, World,
+    Instruction, PanicReason, StorageWrite, Tracer, VirtualMachine, World,
 };
+use zkevm_opcode_defs::{LogOpcode, Opcode};
 
-fn sstore(
+fn sstore<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(
         vm,
@@ -21,68 +23,95 @@ fn sstore(
         world,
         |vm, args, world, continue_normally| {
             if vm.state.current_frame.is_static {
-                return Ok(&PANIC);
+                return panic_with(vm, instruction, PanicReason::StaticContextViolation);
             }
 
             let key = Register1::get(args, &mut vm.state);
             let value = Register2::get(args, &mut vm.state);
+            let contract = vm.state.current_frame.address;
 
-            let (refund, pubdata_change) =
-                vm.world_diff
-                    .write_storage(world, vm.state.current_frame.address, key, value);
+            let previous_value = tracer
+                .wants_previous_storage_values()
+                .then(|| vm.world_diff.peek_storage(world, contract, key));
+            let (refund, pubdata_change, warm) =
+                vm.world_diff.write_storage(world, contract, key, value);
 
             assert!(refund <= SSTORE_COST);
             vm.state.current_frame.gas += refund;
 
             vm.state.current_frame.total_pubdata_spent += pubdata_change;
 
+            tracer.on_storage_write(
+                vm,
+                &StorageWrite {
+                    contract,
+                    key,
+                    previous_value,
+                    value,
+                    refund,
+                    pubdata_change,
+                    warm,
+                },
+            );
+
             continue_normally
         },
     )
 }
 
-fn sstore_transient(
+fn sstore_transient<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate_with_panic(vm, instruction, world, |vm, args, _, continue_normally| {
         if vm.state.current_frame.is_static {
-            return Ok(&PANIC);
+            return panic_with(vm, instruction, PanicReason::StaticContextViolation);
         }
 
         let key = Register1::get(args, &mut vm.state);
         let value = Register2::get(args, &mut vm.state);
 
-        vm.world_diff
-            .write_transient_storage(vm.state.current_frame.address, key, value);
+        let contract = vm.state.current_frame.address;
+
+        let previous_value = tracer
+            .wants_previous_storage_values()
+            .then(|| vm.world_diff.read_transient_storage(contract, key));
+        vm.world_diff.write_transient_storage(contract, key, value);
+
+        tracer.on_transient_storage_write(vm, contract, key, previous_value, value);
 
         continue_normally
     })
 }
 
-fn sload(
+fn sload<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, args, world| {
         let key = Register1::get(args, &mut vm.state);
-        let (value, refund) =
+        let (value, refund, warm) =
             vm.world_diff
                 .read_storage(world, vm.state.current_frame.address, key);
 
         assert!(refund <= SLOAD_COST);
         vm.state.current_frame.gas += refund;
 
+        tracer.on_storage_read(vm, vm.state.current_frame.address, key, value, refund, warm);
+
         Register1::set(args, &mut vm.state, value);
     })
 }
 
-fn sload_transient(
+fn sload_transient<T: Tracer + ?Sized>(
     vm: &mut VirtualMachine,
     instruction: *const Instruction,
     world: &mut dyn World,
+    tracer: &mut T,
 ) -> InstructionResult {
     instruction_boilerplate(vm, instruction, world, |vm, args, _| {
         let key = Register1::get(args, &mut vm.state);
@@ -90,16 +119,21 @@ fn sload_transient(
             .world_diff
             .read_transient_storage(vm.state.current_frame.address, key);
 
+        tracer.on_transient_storage_read(vm, vm.state.current_frame.address, key, value);
+
         Register1::set(args, &mut vm.state, value);
     })
 }
 
+use super::monomorphization::*;
+
 impl Instruction {
     #[inline(always)]
     pub fn from_sstore(src1: Register1, src2: Register2, arguments: Arguments) -> Self {
         Self {
-            handler: sstore,
+            handler: monomorphize!(sstore),
             arguments: arguments.write_source(&src1).write_source(&src2),
+            opcode: Opcode::Log(LogOpcode::StorageWrite),
         }
     }
 }
@@ -108,8 +142,9 @@ impl Instruction {
     #[inline(always)]
     pub fn from_sstore_transient(src1: Register1, src2: Register2, arguments: Arguments) -> Self {
         Self {
-            handler: sstore_transient,
+            handler: monomorphize!(sstore_transient),
             arguments: arguments.write_source(&src1).write_source(&src2),
+            opcode: Opcode::Log(LogOpcode::TransientStorageWrite),
         }
     }
 }
@@ -118,8 +153,9 @@ impl Instruction {
     #[inline(always)]
     pub fn from_sload(src: Register1, dst: Register1, arguments: Arguments) -> Self {
         Self {
-            handler: sload,
+            handler: monomorphize!(sload),
             arguments: arguments.write_source(&src).write_destination(&dst),
+            opcode: Opcode::Log(LogOpcode::StorageRead),
         }
     }
 }
@@ -128,8 +164,9 @@ impl Instruction {
     #[inline(always)]
     pub fn from_sload_transient(src: Register1, dst: Register1, arguments: Arguments) -> Self {
         Self {
-            handler: sload_transient,
+            handler: monomorphize!(sload_transient),
             arguments: arguments.write_source(&src).write_destination(&dst),
+            opcode: Opcode::Log(LogOpcode::TransientStorageRead),
         }
     }
 }
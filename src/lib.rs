@@ -1,32 +1,46 @@
 pub mod addressing_modes;
 #[cfg(feature = "arbitrary")]
 mod arbitrary_instruction;
+mod backtrace;
 mod bitset;
 mod callframe;
 pub mod decode;
 mod decommit;
+mod divergence;
 mod fat_pointer;
 mod instruction;
 pub mod instruction_handlers;
 mod modified_world;
 mod predication;
 mod program;
+mod revert_reason;
 mod rollback;
 mod stack;
 mod state;
 pub mod testworld;
+mod time_travel;
+mod tracer;
+pub mod tracers;
 mod vm;
 
 use u256::{H160, U256};
 
+pub use backtrace::{Backtrace, BacktraceFrame, BacktraceNearCall};
 pub use decommit::address_into_u256;
 pub use decommit::initial_decommit;
-pub use instruction::{jump_to_beginning, ExecutionEnd, Instruction};
+pub use divergence::{compare_traces, find_divergence, Difference, Divergence};
+pub use fat_pointer::FatPointer;
+pub use instruction::{jump_to_beginning, ExecutionEnd, Instruction, PanicReason};
 pub use modified_world::{Event, L2ToL1Log, WorldDiff};
 pub use predication::Predicate;
 pub use program::Program;
-pub use state::{State, FIRST_HEAP};
-pub use vm::{Settings, VirtualMachine, VmSnapshot as Snapshot};
+pub use revert_reason::{describe_panic_code, hex, ErrorRegistry, RevertReason};
+pub use state::{
+    State, FIRST_HEAP, INITIAL_MONOTONIC_CYCLE_COUNTER, STARTING_TIMESTAMP, TIME_DELTA_PER_CYCLE,
+};
+pub use time_travel::TimeTravel;
+pub use tracer::{PrecompileCall, StorageWrite, Tracer};
+pub use vm::{HookHandler, HookResult, Settings, Step, VirtualMachine, VmSnapshot as Snapshot};
 
 pub trait World {
     /// This will be called *every* time a contract is called. Caching and decoding is
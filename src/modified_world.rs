@@ -27,6 +27,7 @@ pub struct WorldDiff {
     written_storage_slots: RollbackableSet<(H160, U256)>,
 }
 
+#[derive(Clone)]
 pub struct ExternalSnapshot {
     internal_snapshot: Snapshot,
     pub(crate) decommitted_hashes: <RollbackableMap<U256, ()> as Rollback>::Snapshot,
@@ -55,30 +56,38 @@ pub struct L2ToL1Log {
 }
 
 impl WorldDiff {
-    /// Returns the storage slot's value and a refund based on its hot/cold status.
+    /// Returns the storage slot's value, a refund based on its hot/cold status
+    /// and whether the slot is warm, meaning it has been read or written before.
+    ///
+    /// Free slots are always refunded as if they were warm, but they are warm only after their first access.
     pub(crate) fn read_storage(
         &mut self,
         world: &mut dyn World,
         contract: H160,
         key: U256,
-    ) -> (U256, u32) {
-        let value = self
-            .storage_changes
-            .as_ref()
-            .get(&(contract, key))
-            .cloned()
-            .unwrap_or_else(|| world.read_storage(contract, key));
+    ) -> (U256, u32, bool) {
+        let value = self.peek_storage(world, contract, key);
 
-        let refund = if world.is_free_storage_slot(&contract, &key)
-            || self.read_storage_slots.contains(&(contract, key))
-        {
+        let warm = self.read_storage_slots.contains(&(contract, key));
+        if !warm {
+            self.read_storage_slots.add((contract, key));
+        }
+        let refund = if warm || world.is_free_storage_slot(&contract, &key) {
             WARM_READ_REFUND
         } else {
-            self.read_storage_slots.add((contract, key));
             0
         };
 
-        (value, refund)
+        (value, refund, warm)
+    }
+
+    /// Returns the storage slot's value without affecting its hot/cold status.
+    pub(crate) fn peek_storage(&self, world: &mut dyn World, contract: H160, key: U256) -> U256 {
+        self.storage_changes
+            .as_ref()
+            .get(&(contract, key))
+            .cloned()
+            .unwrap_or_else(|| world.read_storage(contract, key))
     }
 
     pub(crate) fn read_transient_storage(&mut self, contract: H160, key: U256) -> U256 {
@@ -97,18 +106,27 @@ impl WorldDiff {
             .insert((contract, key), value);
     }
 
-    /// Returns the refund based the hot/cold status of the storage slot and the change in pubdata.
+    /// Returns the refund based the hot/cold status of the storage slot, the change in pubdata
+    /// and whether the slot is warm for writing, meaning it has been written before.
     pub(crate) fn write_storage(
         &mut self,
         world: &mut dyn World,
         contract: H160,
         key: U256,
         value: U256,
-    ) -> (u32, i32) {
+    ) -> (u32, i32, bool) {
         self.storage_changes.insert((contract, key), value);
 
         if world.is_free_storage_slot(&contract, &key) {
-            return (WARM_WRITE_REFUND, 0);
+            let warm = self
+                .written_storage_slots
+                .as_ref()
+                .contains_key(&(contract, key));
+            if !warm {
+                self.written_storage_slots.add((contract, key));
+                self.read_storage_slots.add((contract, key));
+            }
+            return (WARM_WRITE_REFUND, 0, warm);
         }
 
         let update_cost = world.cost_of_writing_storage(contract, key, value);
@@ -117,11 +135,11 @@ impl WorldDiff {
             .insert((contract, key), update_cost)
             .unwrap_or(0);
 
-        let refund = if self
+        let warm = self
             .written_storage_slots
             .as_ref()
-            .contains_key(&(contract, key))
-        {
+            .contains_key(&(contract, key));
+        let refund = if warm {
             WARM_WRITE_REFUND
         } else {
             self.written_storage_slots.add((contract, key));
@@ -134,7 +152,7 @@ impl WorldDiff {
             }
         };
 
-        (refund, (update_cost as i32) - (prepaid as i32))
+        (refund, (update_cost as i32) - (prepaid as i32), warm)
     }
 
     pub fn get_storage_state(&self) -> &BTreeMap<(H160, U256), U256> {
@@ -229,6 +247,36 @@ impl WorldDiff {
         }
     }
 
+    /// Unlike [Self::external_snapshot], this can be called in any frame and keeps transient storage.
+    /// It is only safe to roll back to while [Self::can_roll_back_to] holds.
+    pub(crate) fn checkpoint(&self) -> ExternalSnapshot {
+        ExternalSnapshot {
+            internal_snapshot: self.snapshot(),
+            decommitted_hashes: self.decommitted_hashes.snapshot(),
+            read_storage_slots: self.read_storage_slots.snapshot(),
+            written_storage_slots: self.written_storage_slots.snapshot(),
+        }
+    }
+
+    /// Returns false once history the snapshot depends on has been discarded. That happens when
+    /// a frame that was active when the snapshot was taken fails, when transient storage is cleared
+    /// and when the history is deleted.
+    ///
+    /// Changes made after the snapshot may not have been undone and redone in the meantime,
+    /// so this has to be checked after every instruction.
+    pub(crate) fn can_roll_back_to(&self, snapshot: &ExternalSnapshot) -> bool {
+        let now = self.checkpoint();
+        let (then, now_internal) = (&snapshot.internal_snapshot, &now.internal_snapshot);
+        then.storage_changes <= now_internal.storage_changes
+            && then.events <= now_internal.events
+            && then.l2_to_l1_logs <= now_internal.l2_to_l1_logs
+            && then.paid_changes <= now_internal.paid_changes
+            && then.transient_storage_changes <= now_internal.transient_storage_changes
+            && snapshot.decommitted_hashes <= now.decommitted_hashes
+            && snapshot.read_storage_slots <= now.read_storage_slots
+            && snapshot.written_storage_slots <= now.written_storage_slots
+    }
+
     pub(crate) fn external_rollback(&mut self, snapshot: ExternalSnapshot) {
         self.rollback(snapshot.internal_snapshot);
         self.decommitted_hashes
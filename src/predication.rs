@@ -10,10 +10,23 @@ impl Flags {
     pub fn new(lt_of: bool, eq: bool, gt: bool) -> Self {
         Flags(lt_of as u8 | ((eq as u8) << 1) | ((gt as u8) << 2) | ALWAYS_BIT)
     }
+
+    /// Less than or overflow
+    pub fn lt_of(&self) -> bool {
+        self.0 & LT_BIT != 0
+    }
+
+    pub fn eq(&self) -> bool {
+        self.0 & EQ_BIT != 0
+    }
+
+    pub fn gt(&self) -> bool {
+        self.0 & GT_BIT != 0
+    }
 }
 
 /// Predicate encoded so that comparing it to flags is efficient
-#[derive(Copy, Clone, Debug, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[repr(u8)]
 pub enum Predicate {
@@ -0,0 +1,125 @@
+use crate::ExecutionEnd;
+use std::{collections::HashMap, fmt};
+use u256::U256;
+
+/// The selector of Solidity's `Error(string)`, used by `require` and `revert` with a message.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// The selector of Solidity's `Panic(uint256)`, used by failed assertions and checked arithmetic.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Revert data decoded according to the Solidity ABI.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RevertReason {
+    /// Reverted without any data, for example via `revert()` or a bare `require`.
+    Empty,
+    Error(String),
+    Panic(U256),
+    /// A custom error found in the [ErrorRegistry]. The arguments are still ABI-encoded.
+    Custom {
+        signature: String,
+        arguments: Vec<u8>,
+    },
+    /// Data that doesn't match any known error.
+    Unknown(Vec<u8>),
+}
+
+/// Maps selectors of custom errors to their signatures, like `InsufficientBalance(uint256,uint256)`.
+#[derive(Clone, Default, Debug)]
+pub struct ErrorRegistry(HashMap<[u8; 4], String>);
+
+impl ErrorRegistry {
+    pub fn register(&mut self, selector: [u8; 4], signature: impl Into<String>) {
+        self.0.insert(selector, signature.into());
+    }
+}
+
+impl RevertReason {
+    pub fn decode(data: &[u8], registry: &ErrorRegistry) -> Self {
+        let Some((selector, arguments)) = data.split_first_chunk::<4>() else {
+            return if data.is_empty() {
+                Self::Empty
+            } else {
+                Self::Unknown(data.to_vec())
+            };
+        };
+
+        let decoded = match *selector {
+            ERROR_SELECTOR => decode_string(arguments).map(Self::Error),
+            PANIC_SELECTOR => {
+                (arguments.len() == 32).then(|| Self::Panic(U256::from_big_endian(arguments)))
+            }
+            _ => registry.0.get(selector).map(|signature| Self::Custom {
+                signature: signature.clone(),
+                arguments: arguments.to_vec(),
+            }),
+        };
+        decoded.unwrap_or_else(|| Self::Unknown(data.to_vec()))
+    }
+}
+
+/// Decodes the ABI encoding of a single string argument.
+fn decode_string(arguments: &[u8]) -> Option<String> {
+    let word = |index: usize| -> Option<usize> {
+        let word = U256::from_big_endian(arguments.get(index..index.checked_add(32)?)?);
+        (word <= U256::from(usize::MAX)).then(|| word.as_usize())
+    };
+
+    let offset = word(0)?;
+    let length = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = arguments.get(start..start.checked_add(length)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Describes the panic codes emitted by the Solidity compiler.
+pub fn describe_panic_code(code: U256) -> Option<&'static str> {
+    if code > U256::from(u8::MAX) {
+        return None;
+    }
+    Some(match code.low_u32() {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "conversion to an invalid enum value",
+        0x22 => "incorrectly encoded storage byte array",
+        0x31 => "pop on an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "too much memory allocated",
+        0x51 => "call to a zero-initialized function pointer",
+        _ => return None,
+    })
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "reverted without a reason"),
+            Self::Error(message) => write!(f, "Error({message:?})"),
+            Self::Panic(code) => match describe_panic_code(*code) {
+                Some(description) => write!(f, "Panic({:#04x}): {description}", code.low_u32()),
+                None => write!(f, "Panic({code:#x})"),
+            },
+            Self::Custom {
+                signature,
+                arguments,
+            } => write!(f, "{signature} with arguments 0x{}", hex(arguments)),
+            Self::Unknown(data) => write!(f, "0x{}", hex(data)),
+        }
+    }
+}
+
+/// Formats bytes as lowercase hex without a `0x` prefix.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl ExecutionEnd {
+    /// Decodes the revert data if execution ended with [ExecutionEnd::Reverted].
+    pub fn revert_reason(&self, registry: &ErrorRegistry) -> Option<RevertReason> {
+        match self {
+            Self::Reverted(data) => Some(RevertReason::decode(data, registry)),
+            _ => None,
+        }
+    }
+}
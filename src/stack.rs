@@ -1,5 +1,5 @@
 use crate::bitset::Bitset;
-use std::alloc::{alloc, alloc_zeroed, Layout};
+use std::alloc::{alloc_zeroed, Layout};
 use u256::U256;
 
 #[derive(PartialEq, Debug)]
@@ -18,7 +18,7 @@ impl Stack {
         unsafe { Box::from_raw(alloc_zeroed(Layout::new::<Stack>()).cast::<Stack>()) }
     }
 
-    pub(crate) fn get(&self, slot: u16) -> U256 {
+    pub fn get(&self, slot: u16) -> U256 {
         self.slots[slot as usize]
     }
 
@@ -43,13 +43,19 @@ impl Stack {
     }
 }
 
+/// Only copies the areas that have been written to, as the rest is zero.
 impl Clone for Box<Stack> {
     fn clone(&self) -> Self {
-        unsafe {
-            let allocation = alloc(Layout::for_value(&**self)).cast();
-            std::ptr::copy_nonoverlapping(&**self, allocation, 1);
-            Box::from_raw(allocation)
+        let mut clone = Stack::new();
+        clone.pointer_flags = self.pointer_flags.clone();
+        clone.dirty_areas = self.dirty_areas;
+        for i in 0..NUMBER_OF_DIRTY_AREAS {
+            if self.dirty_areas & (1 << i) != 0 {
+                let area = i * DIRTY_AREA_SIZE..(i + 1) * DIRTY_AREA_SIZE;
+                clone.slots[area.clone()].copy_from_slice(&self.slots[area]);
+            }
         }
+        clone
     }
 }
 
@@ -84,4 +90,12 @@ mod tests {
         let stack = Stack::new();
         let _ = stack.clone();
     }
+
+    #[test]
+    fn clone_copies_written_slots() {
+        let mut stack = Stack::new();
+        stack.set(5, 1.into());
+        stack.set(u16::MAX, 2.into());
+        assert_eq!(stack.clone(), stack);
+    }
 }
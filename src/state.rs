@@ -4,11 +4,13 @@ use crate::{
 };
 use std::ops::{Index, IndexMut};
 use u256::{H160, U256};
+use zk_evm_abstractions::aux::Timestamp;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct State {
     pub registers: [U256; 16],
-    pub(crate) register_pointer_flags: u16,
+    /// Bit `i` is set if register `i` holds a [crate::FatPointer].
+    pub register_pointer_flags: u16,
 
     pub flags: Flags,
 
@@ -22,11 +24,29 @@ pub struct State {
 
     pub transaction_number: u16,
 
+    /// Orders the queries the instructions make.
+    /// Advances by [TIME_DELTA_PER_CYCLE] whenever [State::monotonic_cycle_counter] advances.
+    pub timestamp: u32,
+    /// Advances by one after every instruction, including ones skipped because of their predicate.
+    /// Only instructions executed with a tracer other than `()` count, as only tracers see the queries.
+    ///
+    /// Like in the reference VM, a panic takes a cycle of its own when the instruction that causes it
+    /// has already done something, for example after a failed far call or an invalid jump.
+    /// The panic is then carried out by an instruction that isn't part of the program.
+    /// An instruction that panics without doing anything, like one that can't pay for itself, becomes the panic.
+    pub monotonic_cycle_counter: u32,
+
     pub(crate) context_u128: u128,
 }
 
 pub const FIRST_HEAP: u32 = 2;
 
+/// The timestamp and cycle counter start here, not at zero.
+pub const STARTING_TIMESTAMP: u32 = 1024;
+pub const INITIAL_MONOTONIC_CYCLE_COUNTER: u32 = 1024;
+/// Each instruction gets a timestamp for each of the kinds of queries it can make.
+pub const TIME_DELTA_PER_CYCLE: u32 = 4;
+
 impl State {
     pub(crate) fn new(
         address: H160,
@@ -73,10 +93,54 @@ impl State {
             heaps: Heaps(vec![vec![], calldata, vec![], vec![]]),
 
             transaction_number: 0,
+            timestamp: STARTING_TIMESTAMP,
+            monotonic_cycle_counter: INITIAL_MONOTONIC_CYCLE_COUNTER,
             context_u128: 0,
         }
     }
 
+    /// Used for the operands the current instruction reads.
+    pub fn timestamp_for_code_or_src_read(&self) -> Timestamp {
+        Timestamp(self.timestamp)
+    }
+
+    /// Used for decommits, storage accesses, events, precompile calls and heap reads.
+    pub fn timestamp_for_first_decommit_or_precompile_read(&self) -> Timestamp {
+        Timestamp(self.timestamp + 1)
+    }
+
+    /// Used for heap writes.
+    pub fn timestamp_for_second_decommit_or_precompile_write(&self) -> Timestamp {
+        Timestamp(self.timestamp + 2)
+    }
+
+    /// Used for the result the current instruction writes.
+    pub fn timestamp_for_dst_write(&self) -> Timestamp {
+        Timestamp(self.timestamp + 3)
+    }
+
+    /// Like [Clone::clone] but leaves the heaps empty, for callers that save them in another way.
+    pub(crate) fn clone_without_heaps(&self) -> Self {
+        Self {
+            registers: self.registers,
+            register_pointer_flags: self.register_pointer_flags,
+            flags: self.flags.clone(),
+            current_frame: self.current_frame.clone(),
+            previous_frames: self.previous_frames.clone(),
+            heaps: Heaps(vec![]),
+            transaction_number: self.transaction_number,
+            timestamp: self.timestamp,
+            monotonic_cycle_counter: self.monotonic_cycle_counter,
+            context_u128: self.context_u128,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn advance_cycle(&mut self) {
+        self.timestamp = self.timestamp.wrapping_add(TIME_DELTA_PER_CYCLE);
+        self.monotonic_cycle_counter = self.monotonic_cycle_counter.wrapping_add(1);
+    }
+
     #[inline(always)]
     pub(crate) fn use_gas(&mut self, amount: u32) -> Result<(), ()> {
         if self.current_frame.gas >= amount {
@@ -98,6 +162,17 @@ impl State {
                 .sum::<u32>()
     }
 
+    /// The gas the VM's callers would get back if all frames returned right now.
+    ///
+    /// Unlike [State::total_unspent_gas], this excludes stipends and includes gas held back by
+    /// near calls in the current frame, so it only changes when gas is spent or refunded.
+    pub(crate) fn total_gas_without_stipends(&self) -> u64 {
+        std::iter::once(&self.current_frame)
+            .chain(self.previous_frames.iter().map(|(_, frame)| frame))
+            .map(|frame| frame.contained_gas().saturating_sub(frame.stipend) as u64)
+            .sum()
+    }
+
     pub(crate) fn set_context_u128(&mut self, value: u128) {
         self.context_u128 = value;
     }
@@ -136,9 +211,89 @@ impl Addressable for State {
 pub struct Heaps(pub(crate) Vec<Vec<u8>>);
 
 impl Heaps {
+    pub fn get(&self, heap: u32) -> Option<&[u8]> {
+        self.0.get(heap as usize).map(|heap| heap.as_slice())
+    }
+
     pub(crate) fn deallocate(&mut self, heap: u32) {
         self.0[heap as usize] = vec![];
     }
+
+    /// Records the 32-byte words in which `self` differs from `base`, so that
+    /// [Heaps::apply_diff] can recreate `self` from `base`.
+    /// Bytes past the end of a heap in `base` count as zero.
+    pub(crate) fn diff(&self, base: &Heaps) -> HeapsDiff {
+        let changed =
+            self.0
+                .iter()
+                .enumerate()
+                .filter_map(|(index, heap)| {
+                    let base = base.0.get(index).map_or(&[][..], Vec::as_slice);
+                    if heap.as_slice() == base {
+                        return None;
+                    }
+
+                    let mut runs: Vec<(usize, Vec<u8>)> = vec![];
+                    for (i, word) in heap.chunks(32).enumerate() {
+                        let start = i * 32;
+                        let unchanged = match base.get(start..start + word.len()) {
+                            Some(before) => before == word,
+                            None => word.iter().enumerate().all(|(j, byte)| {
+                                *byte == base.get(start + j).copied().unwrap_or(0)
+                            }),
+                        };
+                        if unchanged {
+                            continue;
+                        }
+                        match runs.last_mut() {
+                            Some((run_start, bytes)) if *run_start + bytes.len() == start => {
+                                bytes.extend_from_slice(word)
+                            }
+                            _ => runs.push((start, word.to_vec())),
+                        }
+                    }
+                    Some(ChangedHeap {
+                        index,
+                        length: heap.len(),
+                        runs,
+                    })
+                })
+                .collect();
+
+        HeapsDiff {
+            heap_count: self.0.len(),
+            changed,
+        }
+    }
+
+    /// Returns the heaps `diff` was made from, given the `base` it was made against.
+    pub(crate) fn apply_diff(&self, diff: &HeapsDiff) -> Heaps {
+        let mut heaps = self.0.clone();
+        heaps.resize(diff.heap_count, vec![]);
+        for changed in &diff.changed {
+            let heap = &mut heaps[changed.index];
+            heap.resize(changed.length, 0);
+            for (start, bytes) in &changed.runs {
+                heap[*start..start + bytes.len()].copy_from_slice(bytes);
+            }
+        }
+        Heaps(heaps)
+    }
+}
+
+/// See [Heaps::diff].
+#[derive(Clone, Debug)]
+pub(crate) struct HeapsDiff {
+    heap_count: usize,
+    changed: Vec<ChangedHeap>,
+}
+
+#[derive(Clone, Debug)]
+struct ChangedHeap {
+    index: usize,
+    length: usize,
+    /// The changed bytes and where they start, in runs of whole words.
+    runs: Vec<(usize, Vec<u8>)>,
 }
 
 impl Index<u32> for Heaps {
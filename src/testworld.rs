@@ -3,7 +3,7 @@ use std::{
     collections::{hash_map::DefaultHasher, BTreeMap},
     hash::{Hash, Hasher},
 };
-use u256::U256;
+use u256::{H160, U256};
 use zkevm_opcode_defs::{
     ethereum_types::Address, system_params::DEPLOYER_SYSTEM_CONTRACT_ADDRESS_LOW,
 };
@@ -11,6 +11,8 @@ use zkevm_opcode_defs::{
 pub struct TestWorld {
     pub address_to_hash: BTreeMap<U256, U256>,
     pub hash_to_contract: BTreeMap<U256, Program>,
+    /// Storage values before execution. Slots that aren't listed are zero.
+    pub storage: BTreeMap<(H160, U256), U256>,
 }
 
 impl TestWorld {
@@ -35,6 +37,7 @@ impl TestWorld {
         Self {
             address_to_hash,
             hash_to_contract,
+            storage: BTreeMap::new(),
         }
     }
 }
@@ -58,7 +61,10 @@ impl World for TestWorld {
                 .copied()
                 .unwrap_or(U256::zero())
         } else {
-            0.into()
+            self.storage
+                .get(&(contract, key))
+                .copied()
+                .unwrap_or_default()
         }
     }
 
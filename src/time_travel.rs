@@ -0,0 +1,182 @@
+use crate::{
+    backtrace::Backtrace,
+    state::{Heaps, HeapsDiff},
+    vm::{Step, VmSnapshot},
+    Tracer, VirtualMachine, World,
+};
+
+/// Steps through a program while recording checkpoints, so that execution can be rewound.
+///
+/// Going to an earlier instruction restores the closest checkpoint before it and replays
+/// from there, so the world has to answer the same way every time.
+pub struct TimeTravel {
+    interval: u64,
+    checkpoints: Vec<Checkpoint>,
+
+    /// The number of instructions executed since recording started.
+    position: u64,
+    next: Step,
+}
+
+struct Checkpoint {
+    position: u64,
+    next: Step,
+    vm: VmSnapshot,
+    heaps: SavedHeaps,
+    failure_backtrace: Option<Backtrace>,
+}
+
+/// The heaps are copied in full at every this many checkpoints.
+const CHECKPOINTS_PER_FULL_HEAPS: usize = 16;
+
+enum SavedHeaps {
+    Full(Heaps),
+    /// The difference to the closest earlier checkpoint with [SavedHeaps::Full].
+    Diff(HeapsDiff),
+}
+
+impl TimeTravel {
+    /// Starts recording at instruction `pc` of the VM's current frame.
+    /// A checkpoint is recorded every `interval` instructions.
+    ///
+    /// Checkpoints refer to the world's rollback log instead of copying storage. Most of them
+    /// only store the parts of the heaps that changed since the last one that copied them in full.
+    pub fn new(vm: &VirtualMachine, pc: u16, interval: u64) -> Self {
+        assert!(interval != 0, "the checkpoint interval must not be zero");
+        let next = Step::Continue {
+            pc,
+            frame: vm.state.previous_frames.len(),
+        };
+        let mut time_travel = Self {
+            interval,
+            checkpoints: vec![],
+            position: 0,
+            next,
+        };
+        time_travel.record_checkpoint(vm);
+        time_travel
+    }
+
+    /// The number of instructions executed since recording started.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The instruction to execute next or how execution ended.
+    pub fn next(&self) -> &Step {
+        &self.next
+    }
+
+    /// Executes the next instruction, like [VirtualMachine::step].
+    /// Does nothing once execution has ended.
+    pub fn step<T: Tracer>(
+        &mut self,
+        vm: &mut VirtualMachine,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> Step {
+        let Step::Continue { pc, .. } = self.next else {
+            return self.next.clone();
+        };
+        self.next = vm.step(pc, world, tracer);
+        self.position += 1;
+
+        // Checkpoints taken inside a frame that just failed can't be returned to.
+        // Earlier checkpoints are unaffected because the world diff only grows between them.
+        while self
+            .checkpoints
+            .last()
+            .is_some_and(|checkpoint| !vm.can_roll_back_to(&checkpoint.vm))
+        {
+            self.checkpoints.pop();
+        }
+
+        let due = match self.checkpoints.last() {
+            Some(checkpoint) => self.position >= checkpoint.position + self.interval,
+            None => true,
+        };
+        if due && matches!(self.next, Step::Continue { .. }) {
+            self.record_checkpoint(vm);
+        }
+
+        self.next.clone()
+    }
+
+    fn record_checkpoint(&mut self, vm: &VirtualMachine) {
+        let last_full = self
+            .checkpoints
+            .iter()
+            .rev()
+            .take(CHECKPOINTS_PER_FULL_HEAPS - 1)
+            .find_map(|checkpoint| match &checkpoint.heaps {
+                SavedHeaps::Full(heaps) => Some(heaps),
+                SavedHeaps::Diff(_) => None,
+            });
+        let heaps = match last_full {
+            Some(base) => SavedHeaps::Diff(vm.state.heaps.diff(base)),
+            None => SavedHeaps::Full(vm.state.heaps.clone()),
+        };
+
+        self.checkpoints.push(Checkpoint {
+            position: self.position,
+            next: self.next.clone(),
+            vm: vm.checkpoint(),
+            heaps,
+            failure_backtrace: vm.failure_backtrace.clone(),
+        });
+    }
+
+    /// The heaps as they were at the checkpoint at `index`.
+    fn heaps_at(&self, index: usize) -> Heaps {
+        let mut diff = None;
+        for checkpoint in self.checkpoints[..=index].iter().rev() {
+            match &checkpoint.heaps {
+                SavedHeaps::Full(heaps) => {
+                    return match diff {
+                        Some(diff) => heaps.apply_diff(diff),
+                        None => heaps.clone(),
+                    }
+                }
+                SavedHeaps::Diff(d) => {
+                    diff.get_or_insert(d);
+                }
+            }
+        }
+        unreachable!("checkpoints are only removed from the end, so the first one is always full")
+    }
+
+    /// Brings the VM to the point where `position` instructions had been executed since recording started.
+    /// Going backwards restores the closest earlier checkpoint, then the instructions
+    /// up to `position` are executed again and reported to `tracer`.
+    ///
+    /// Stops early if execution ends. Returns `None` if there is no checkpoint to go back to,
+    /// which happens when recording started in a frame that has since failed.
+    pub fn go_to<T: Tracer>(
+        &mut self,
+        vm: &mut VirtualMachine,
+        world: &mut dyn World,
+        tracer: &mut T,
+        position: u64,
+    ) -> Option<Step> {
+        if position < self.position {
+            let index = self
+                .checkpoints
+                .partition_point(|checkpoint| checkpoint.position <= position)
+                .checked_sub(1)?;
+            self.checkpoints.truncate(index + 1);
+
+            let heaps = self.heaps_at(index);
+            let checkpoint = &self.checkpoints[index];
+            vm.restore_with_heaps(checkpoint.vm.clone(), heaps);
+            vm.failure_backtrace
+                .clone_from(&checkpoint.failure_backtrace);
+            self.position = checkpoint.position;
+            self.next = checkpoint.next.clone();
+        }
+
+        while self.position < position && matches!(self.next, Step::Continue { .. }) {
+            self.step(vm, world, tracer);
+        }
+        Some(self.next.clone())
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    instruction_handlers::{CallingMode, ReturnType},
+    modified_world::{Event, L2ToL1Log},
+    ExecutionEnd, PanicReason, VirtualMachine,
+};
+use u256::{H160, U256};
+use zk_evm_abstractions::queries::{LogQuery, MemoryQuery};
+
+/// Observes execution without being able to influence it, except for stopping it
+/// in a way that allows resuming. See [Tracer::should_stop].
+///
+/// The run loop is generic over the tracer, so the per-instruction callbacks
+/// of a tracer that doesn't override them (like `()`) compile to nothing.
+/// The remaining callbacks are invoked from instruction handlers, which get the
+/// tracer as a trait object just like the [crate::World].
+///
+/// All methods do nothing by default.
+pub trait Tracer {
+    /// Polled before every instruction, even before [Tracer::before_instruction].
+    /// Returning true ends execution with [crate::ExecutionEnd::StoppedByTracer]
+    /// without executing the instruction at `pc`.
+    #[inline(always)]
+    fn should_stop(&mut self, _vm: &VirtualMachine, _pc: u16) -> bool {
+        false
+    }
+
+    /// Called before the instruction at `pc` in the current frame pays its static gas cost.
+    #[inline(always)]
+    fn before_instruction(&mut self, _vm: &VirtualMachine, _pc: u16) {}
+
+    /// Called after the instruction at `pc` has been executed or skipped because of its predicate.
+    ///
+    /// Note that `pc` refers to the frame the instruction was executed in,
+    /// which is no longer the current frame after a far call or return.
+    #[inline(always)]
+    fn after_instruction(&mut self, _vm: &VirtualMachine, _pc: u16) {}
+
+    /// Called when a method that runs the VM, like [VirtualMachine::run], returns `end`.
+    /// Not called for [crate::Step::Continue] or when the extra gas limit of
+    /// [VirtualMachine::resume_with_additional_gas_limit] is exceeded.
+    fn on_execution_end(&mut self, _vm: &VirtualMachine, _end: &ExecutionEnd) {}
+
+    /// Called after a far call has pushed the new frame, before the callee's first instruction.
+    fn on_far_call(&mut self, _vm: &VirtualMachine, _mode: CallingMode) {}
+
+    /// Called when a far call to `address` fails before the called frame is created,
+    /// for example because the calldata is invalid or the code can't be decommitted.
+    /// `gas` is the gas that was passed to the call and lost with it.
+    ///
+    /// The current frame is the caller. [Tracer::on_panic] is called right after this.
+    fn on_failed_far_call(
+        &mut self,
+        _vm: &VirtualMachine,
+        _mode: CallingMode,
+        _address: H160,
+        _gas: u32,
+        _reason: PanicReason,
+    ) {
+    }
+
+    /// Called when a far call has loaded the code with `code_hash`, before the called frame is created.
+    /// The call may still fail afterwards.
+    ///
+    /// `is_fresh` is true if the code hadn't been decommitted before, so its cost was paid.
+    fn on_decommit(&mut self, _vm: &VirtualMachine, _code_hash: U256, _is_fresh: bool) {}
+
+    /// Called before the current frame returns to its caller. The returning frame is still the current frame.
+    ///
+    /// `return_data` is empty if the frame panics.
+    fn on_far_return(
+        &mut self,
+        _vm: &VirtualMachine,
+        _return_type: ReturnType,
+        _return_data: &[u8],
+    ) {
+    }
+
+    /// Called when the current frame panics, before [Tracer::on_near_return] or [Tracer::on_far_return].
+    ///
+    /// A far call that fails before the called frame is created doesn't return.
+    /// In that case this follows [Tracer::on_failed_far_call] and the current frame is the caller.
+    fn on_panic(&mut self, _vm: &VirtualMachine, _reason: PanicReason) {}
+
+    /// Called after a near call has been entered, before the first instruction of the called function.
+    /// `destination` is the label of that instruction.
+    fn on_near_call(&mut self, _vm: &VirtualMachine, _destination: u16) {}
+
+    /// Called before a near call returns. The near call is still on the stack.
+    fn on_near_return(&mut self, _vm: &VirtualMachine, _return_type: ReturnType) {}
+
+    /// `refund` is the gas refunded because the slot had already been accessed or is free.
+    /// `warm` is true if the slot had already been read or written.
+    fn on_storage_read(
+        &mut self,
+        _vm: &VirtualMachine,
+        _contract: H160,
+        _key: U256,
+        _value: U256,
+        _refund: u32,
+        _warm: bool,
+    ) {
+    }
+
+    /// Called after the value has been written.
+    fn on_storage_write(&mut self, _vm: &VirtualMachine, _write: &StorageWrite) {}
+
+    /// Whether [StorageWrite::previous_value] and the previous value passed to
+    /// [Tracer::on_transient_storage_write] should be filled in.
+    /// Finding it may take an extra read from the [crate::World].
+    fn wants_previous_storage_values(&self) -> bool {
+        false
+    }
+
+    fn on_transient_storage_read(
+        &mut self,
+        _vm: &VirtualMachine,
+        _contract: H160,
+        _key: U256,
+        _value: U256,
+    ) {
+    }
+
+    fn on_transient_storage_write(
+        &mut self,
+        _vm: &VirtualMachine,
+        _contract: H160,
+        _key: U256,
+        _previous_value: Option<U256>,
+        _value: U256,
+    ) {
+    }
+
+    /// Called after the 32-byte big-endian `value` has been read from `heap` at byte offset `address`,
+    /// either by a heap read or through a fat pointer.
+    fn on_heap_read(&mut self, _vm: &VirtualMachine, _heap: u32, _address: u32, _value: U256) {}
+
+    /// Called after the 32-byte big-endian `value` has been written to `heap` at byte offset `address`,
+    /// either by a heap write or by a precompile. Precompiles report their writes before [Tracer::on_precompile_call].
+    fn on_heap_write(&mut self, _vm: &VirtualMachine, _heap: u32, _address: u32, _value: U256) {}
+
+    /// Called after a precompile has been run.
+    /// A call from an address that isn't a precompile only burns gas and takes zero rounds.
+    fn on_precompile_call(&mut self, _vm: &VirtualMachine, _call: &PrecompileCall) {}
+
+    /// Whether precompiles should report the heap reads and writes they make to [Tracer::on_precompile_call].
+    /// Collecting them makes precompiles slower.
+    fn wants_precompile_memory_queries(&self) -> bool {
+        false
+    }
+
+    fn on_event(&mut self, _vm: &VirtualMachine, _event: &Event) {}
+
+    fn on_l2_to_l1_log(&mut self, _vm: &VirtualMachine, _log: &L2ToL1Log) {}
+
+    /// Whether all methods of this tracer do nothing.
+    /// The VM then runs instructions that don't call the tracer at all.
+    #[doc(hidden)]
+    fn is_noop(&self) -> bool {
+        false
+    }
+}
+
+/// A write to persistent storage as reported to [Tracer::on_storage_write].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StorageWrite {
+    pub contract: H160,
+    pub key: U256,
+    /// `None` unless [Tracer::wants_previous_storage_values].
+    pub previous_value: Option<U256>,
+    pub value: U256,
+    /// The gas refunded because the slot had already been accessed or is free.
+    pub refund: u32,
+    /// Negative if the slot was written before and that write needed more pubdata.
+    pub pubdata_change: i32,
+    /// True if the slot had already been written.
+    pub warm: bool,
+}
+
+/// A precompile call as reported to [Tracer::on_precompile_call].
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrecompileCall {
+    /// The address of the precompile, which is the current frame's address.
+    pub address: H160,
+    /// The query the precompile was called with.
+    pub query: LogQuery,
+    /// How many rounds of the precompile's circuit the call takes.
+    pub rounds: usize,
+    /// The heap accesses the precompile made, each paired with the [crate::State::monotonic_cycle_counter]
+    /// it was made in. Empty unless [Tracer::wants_precompile_memory_queries].
+    pub memory_reads: Vec<(u32, MemoryQuery)>,
+    pub memory_writes: Vec<(u32, MemoryQuery)>,
+}
+
+/// The tracer that does nothing.
+impl Tracer for () {
+    fn is_noop(&self) -> bool {
+        true
+    }
+}
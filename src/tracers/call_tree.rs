@@ -0,0 +1,282 @@
+use crate::{
+    decommit::u256_into_address,
+    fat_pointer::FatPointer,
+    instruction_handlers::{CallingMode, ReturnType},
+    revert_reason::hex,
+    ErrorRegistry, PanicReason, RevertReason, Tracer, VirtualMachine,
+};
+use u256::H160;
+
+/// Records the tree of far calls made during execution.
+pub struct CallTracer {
+    /// The calls that haven't returned yet, outermost first.
+    /// Finished calls are moved into their caller, except for the initial one.
+    stack: Vec<Call>,
+    root: Option<Call>,
+
+    /// [Tracer::on_panic] is called before the frame returns.
+    last_panic: Option<PanicReason>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Call {
+    pub mode: CallingMode,
+    pub address: H160,
+    pub code_address: H160,
+    pub caller: H160,
+    pub calldata: Vec<u8>,
+
+    /// The gas passed by the caller, not including the stipend.
+    pub gas: u32,
+    /// How much of [Call::gas] was not returned to the caller.
+    pub gas_used: u32,
+    /// True for calls to the EVM interpreter, which get extra gas that the caller doesn't pay for.
+    pub stipend_applied: bool,
+
+    pub context_u128: u128,
+    pub is_static: bool,
+
+    /// `None` while the call is still being executed.
+    pub outcome: Option<CallOutcome>,
+    pub calls: Vec<Call>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CallOutcome {
+    Returned(Vec<u8>),
+    Reverted(Vec<u8>),
+    Panicked(PanicReason),
+}
+
+impl CallTracer {
+    /// The initial frame of `vm` becomes the root of the tree.
+    pub fn new(vm: &VirtualMachine) -> Self {
+        Self {
+            stack: vec![Call::from_current_frame(vm, CallingMode::Normal)],
+            root: None,
+            last_panic: None,
+        }
+    }
+
+    /// The initial call once it has returned.
+    pub fn root(&self) -> Option<&Call> {
+        self.root.as_ref()
+    }
+
+    /// The initial call, even if it hasn't returned yet. Calls that are in progress have no outcome.
+    pub fn into_root(mut self) -> Call {
+        while self.stack.len() > 1 {
+            let call = self.stack.pop().unwrap();
+            self.stack.last_mut().unwrap().calls.push(call);
+        }
+        self.root.or(self.stack.pop()).unwrap()
+    }
+}
+
+impl Call {
+    fn from_current_frame(vm: &VirtualMachine, mode: CallingMode) -> Self {
+        let frame = &vm.state.current_frame;
+
+        // At the start of a frame, r1 always holds the calldata pointer.
+        let calldata = FatPointer::from(vm.state.registers[1]);
+        let calldata = vm.state.heaps[calldata.memory_page]
+            [calldata.start as usize..(calldata.start + calldata.length) as usize]
+            .to_vec();
+
+        Self {
+            mode,
+            address: frame.address,
+            code_address: frame.code_address,
+            caller: frame.caller,
+            calldata,
+            gas: frame.gas - frame.stipend,
+            gas_used: 0,
+            stipend_applied: frame.stipend != 0,
+            context_u128: frame.context_u128,
+            is_static: frame.is_static,
+            outcome: None,
+            calls: vec![],
+        }
+    }
+}
+
+impl Tracer for CallTracer {
+    fn on_far_call(&mut self, vm: &VirtualMachine, mode: CallingMode) {
+        self.stack.push(Call::from_current_frame(vm, mode));
+    }
+
+    fn on_failed_far_call(
+        &mut self,
+        vm: &VirtualMachine,
+        mode: CallingMode,
+        address: H160,
+        gas: u32,
+        reason: PanicReason,
+    ) {
+        let frame = &vm.state.current_frame;
+        let call = Call {
+            mode,
+            address: if mode == CallingMode::Delegate {
+                frame.address
+            } else {
+                address
+            },
+            code_address: address,
+            caller: match mode {
+                CallingMode::Normal => frame.address,
+                CallingMode::Delegate => frame.caller,
+                CallingMode::Mimic => u256_into_address(vm.state.registers[15]),
+            },
+            calldata: vec![],
+            gas,
+            gas_used: gas,
+            stipend_applied: false,
+            context_u128: if mode == CallingMode::Delegate {
+                frame.context_u128
+            } else {
+                vm.state.context_u128
+            },
+            // Whether the call itself was static is not known, only whether the caller is.
+            is_static: frame.is_static,
+            outcome: Some(CallOutcome::Panicked(reason)),
+            calls: vec![],
+        };
+        if let Some(caller) = self.stack.last_mut() {
+            caller.calls.push(call);
+        }
+    }
+
+    fn on_panic(&mut self, _: &VirtualMachine, reason: PanicReason) {
+        self.last_panic = Some(reason);
+    }
+
+    fn on_far_return(&mut self, vm: &VirtualMachine, return_type: ReturnType, return_data: &[u8]) {
+        let Some(mut call) = self.stack.pop() else {
+            return;
+        };
+
+        let frame = &vm.state.current_frame;
+        call.gas_used = call
+            .gas
+            .saturating_sub(frame.gas.saturating_sub(frame.stipend));
+        call.outcome = Some(match return_type {
+            ReturnType::Normal => CallOutcome::Returned(return_data.to_vec()),
+            ReturnType::Revert => CallOutcome::Reverted(return_data.to_vec()),
+            ReturnType::Panic => CallOutcome::Panicked(
+                self.last_panic
+                    .take()
+                    .expect("on_panic is called before a panicking frame returns"),
+            ),
+        });
+
+        match self.stack.last_mut() {
+            Some(caller) => caller.calls.push(call),
+            None => self.root = Some(call),
+        }
+    }
+}
+
+impl Call {
+    /// Formats the call and its subcalls like geth's `callTracer` does for `debug_traceTransaction`.
+    ///
+    /// Calls that fail because of a revert get a `revertReason` if the revert data can be decoded.
+    pub fn to_geth_json(&self, registry: &ErrorRegistry) -> String {
+        let mut out = String::new();
+        self.write_geth_json(&mut out, registry, false);
+        out
+    }
+
+    fn write_geth_json(&self, out: &mut String, registry: &ErrorRegistry, caller_is_static: bool) {
+        let call_type = match self.mode {
+            CallingMode::Delegate => "DELEGATECALL",
+            // Calls made in static mode are static too, but only the one that entered it is a STATICCALL.
+            _ if self.is_static && !caller_is_static => "STATICCALL",
+            _ => "CALL",
+        };
+        // Like in the EVM, a delegate call is made from the delegating contract to the code it borrows.
+        let (from, to) = match self.mode {
+            CallingMode::Delegate => (self.address, self.code_address),
+            _ => (self.caller, self.address),
+        };
+        out.push_str(&format!(
+            r#"{{"type":"{call_type}","from":"{from:#x}","to":"{to:#x}","input":"0x{}","gas":"{:#x}","gasUsed":"{:#x}""#,
+            hex(&self.calldata),
+            self.gas,
+            self.gas_used
+        ));
+
+        match &self.outcome {
+            Some(CallOutcome::Returned(output)) => {
+                out.push_str(&format!(r#","output":"0x{}""#, hex(output)));
+            }
+            Some(CallOutcome::Reverted(output)) => {
+                out.push_str(&format!(
+                    r#","output":"0x{}","error":"execution reverted""#,
+                    hex(output)
+                ));
+                match RevertReason::decode(output, registry) {
+                    RevertReason::Empty | RevertReason::Unknown(_) => {}
+                    RevertReason::Error(message) => {
+                        out.push_str(r#","revertReason":"#);
+                        write_json_string(out, &message);
+                    }
+                    reason => {
+                        out.push_str(r#","revertReason":"#);
+                        write_json_string(out, &reason.to_string());
+                    }
+                }
+            }
+            Some(CallOutcome::Panicked(reason)) => {
+                out.push_str(&format!(r#","error":"{}""#, geth_error(*reason)));
+            }
+            None => {}
+        }
+
+        if !self.calls.is_empty() {
+            out.push_str(r#","calls":["#);
+            for (i, call) in self.calls.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                call.write_geth_json(out, registry, self.is_static);
+            }
+            out.push(']');
+        }
+        out.push('}');
+    }
+}
+
+/// The closest equivalent of geth's error messages.
+fn geth_error(reason: PanicReason) -> &'static str {
+    match reason {
+        PanicReason::OutOfGas => "out of gas",
+        PanicReason::StaticContextViolation => "write protection",
+        PanicReason::IncorrectPointerTags => "incorrect pointer tags",
+        PanicReason::PointerOverflow => "pointer overflow",
+        PanicReason::HeapOffsetTooLarge => "heap offset too large",
+        PanicReason::InvalidCalldata => "invalid calldata",
+        PanicReason::InvalidReturnData => "invalid return data",
+        PanicReason::DecommitFailed => "failed to decommit code",
+        PanicReason::InvalidJump => "invalid jump destination",
+        PanicReason::InvalidInstruction => "invalid opcode",
+        PanicReason::ExplicitPanic => "panic",
+        PanicReason::UnimplementedOpcode => "unimplemented opcode",
+    }
+}
+
+/// Appends `s` to `out` as a quoted and escaped JSON string.
+pub fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str(r#"\""#),
+            '\\' => out.push_str(r"\\"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            '\t' => out.push_str(r"\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!(r"\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,161 @@
+use crate::{
+    instruction_handlers::CallingMode, Event, L2ToL1Log, PanicReason, PrecompileCall, StorageWrite,
+    Tracer, VirtualMachine,
+};
+use std::collections::BTreeMap;
+use u256::{H160, U256};
+use zkevm_opcode_defs::system_params::{
+    ECRECOVER_INNER_FUNCTION_PRECOMPILE_ADDRESS, KECCAK256_ROUND_FUNCTION_PRECOMPILE_ADDRESS,
+    SECP256R1_VERIFY_PRECOMPILE_ADDRESS, SHA256_ROUND_FUNCTION_PRECOMPILE_ADDRESS,
+};
+
+/// Counts the work that determines how many circuits proving the execution takes,
+/// separately for each transaction.
+///
+/// Everything that was executed counts, even if it was rolled back later.
+#[derive(Default)]
+pub struct CircuitEstimator {
+    transactions: BTreeMap<u16, CircuitUsage>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct CircuitUsage {
+    /// Instructions executed or skipped because of their predicate.
+    pub cycles: u64,
+    /// Decommits of code that hadn't been decommitted before.
+    pub decommits: u64,
+    pub decommitted_code_words: u64,
+    /// Includes the read of the code info every far call makes.
+    pub storage_reads: u64,
+    pub storage_writes: u64,
+    pub transient_storage_reads: u64,
+    pub transient_storage_writes: u64,
+    pub events: u64,
+    pub l2_to_l1_logs: u64,
+    pub keccak256_rounds: u64,
+    pub sha256_rounds: u64,
+    pub ecrecover_rounds: u64,
+    pub secp256r1_verify_rounds: u64,
+}
+
+impl CircuitEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The usage of each transaction that did anything, by transaction number.
+    pub fn transactions(&self) -> &BTreeMap<u16, CircuitUsage> {
+        &self.transactions
+    }
+
+    /// The usage of all transactions combined.
+    pub fn total(&self) -> CircuitUsage {
+        self.transactions
+            .values()
+            .fold(CircuitUsage::default(), |total, usage| total + *usage)
+    }
+
+    fn usage(&mut self, vm: &VirtualMachine) -> &mut CircuitUsage {
+        self.transactions
+            .entry(vm.state.transaction_number)
+            .or_default()
+    }
+}
+
+impl std::ops::Add for CircuitUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            cycles: self.cycles + other.cycles,
+            decommits: self.decommits + other.decommits,
+            decommitted_code_words: self.decommitted_code_words + other.decommitted_code_words,
+            storage_reads: self.storage_reads + other.storage_reads,
+            storage_writes: self.storage_writes + other.storage_writes,
+            transient_storage_reads: self.transient_storage_reads + other.transient_storage_reads,
+            transient_storage_writes: self.transient_storage_writes
+                + other.transient_storage_writes,
+            events: self.events + other.events,
+            l2_to_l1_logs: self.l2_to_l1_logs + other.l2_to_l1_logs,
+            keccak256_rounds: self.keccak256_rounds + other.keccak256_rounds,
+            sha256_rounds: self.sha256_rounds + other.sha256_rounds,
+            ecrecover_rounds: self.ecrecover_rounds + other.ecrecover_rounds,
+            secp256r1_verify_rounds: self.secp256r1_verify_rounds + other.secp256r1_verify_rounds,
+        }
+    }
+}
+
+impl Tracer for CircuitEstimator {
+    fn before_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        self.usage(vm).cycles += 1;
+    }
+
+    fn on_far_call(&mut self, vm: &VirtualMachine, _: CallingMode) {
+        self.usage(vm).storage_reads += 1;
+    }
+
+    fn on_failed_far_call(
+        &mut self,
+        vm: &VirtualMachine,
+        _: CallingMode,
+        _: H160,
+        _: u32,
+        _: PanicReason,
+    ) {
+        self.usage(vm).storage_reads += 1;
+    }
+
+    fn on_decommit(&mut self, vm: &VirtualMachine, code_hash: U256, is_fresh: bool) {
+        if is_fresh {
+            let mut hash = [0; 32];
+            code_hash.to_big_endian(&mut hash);
+            let usage = self.usage(vm);
+            usage.decommits += 1;
+            usage.decommitted_code_words += u16::from_be_bytes([hash[2], hash[3]]) as u64;
+        }
+    }
+
+    fn on_storage_read(&mut self, vm: &VirtualMachine, _: H160, _: U256, _: U256, _: u32, _: bool) {
+        self.usage(vm).storage_reads += 1;
+    }
+
+    fn on_storage_write(&mut self, vm: &VirtualMachine, _: &StorageWrite) {
+        self.usage(vm).storage_writes += 1;
+    }
+
+    fn on_transient_storage_read(&mut self, vm: &VirtualMachine, _: H160, _: U256, _: U256) {
+        self.usage(vm).transient_storage_reads += 1;
+    }
+
+    fn on_transient_storage_write(
+        &mut self,
+        vm: &VirtualMachine,
+        _: H160,
+        _: U256,
+        _: Option<U256>,
+        _: U256,
+    ) {
+        self.usage(vm).transient_storage_writes += 1;
+    }
+
+    fn on_precompile_call(&mut self, vm: &VirtualMachine, call: &PrecompileCall) {
+        let address_low = u16::from_be_bytes([call.address[18], call.address[19]]);
+        let usage = self.usage(vm);
+        let counter = match address_low {
+            KECCAK256_ROUND_FUNCTION_PRECOMPILE_ADDRESS => &mut usage.keccak256_rounds,
+            SHA256_ROUND_FUNCTION_PRECOMPILE_ADDRESS => &mut usage.sha256_rounds,
+            ECRECOVER_INNER_FUNCTION_PRECOMPILE_ADDRESS => &mut usage.ecrecover_rounds,
+            SECP256R1_VERIFY_PRECOMPILE_ADDRESS => &mut usage.secp256r1_verify_rounds,
+            _ => return,
+        };
+        *counter += call.rounds as u64;
+    }
+
+    fn on_event(&mut self, vm: &VirtualMachine, _: &Event) {
+        self.usage(vm).events += 1;
+    }
+
+    fn on_l2_to_l1_log(&mut self, vm: &VirtualMachine, _: &L2ToL1Log) {
+        self.usage(vm).l2_to_l1_logs += 1;
+    }
+}
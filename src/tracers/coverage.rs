@@ -0,0 +1,178 @@
+use crate::{revert_reason::hex, Predicate, Tracer, VirtualMachine};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+use u256::U256;
+
+/// Counts how often each instruction of each program was reached.
+///
+/// Programs are identified by the hash they were decommitted from. The initial frame's
+/// program is passed to the VM directly, so its hash has to be supplied when creating the tracer.
+pub struct CoverageTracer {
+    initial_code_hash: U256,
+    programs: HashMap<U256, ProgramCoverage>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ProgramCoverage {
+    /// How often each instruction was reached, indexed by pc.
+    /// Instructions skipped because of their predicate count as reached.
+    pub hits: Vec<u64>,
+    /// Outcomes of the instructions that have a predicate other than [Predicate::Always].
+    pub branches: BTreeMap<u16, BranchCoverage>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct BranchCoverage {
+    /// How often the predicate held and the instruction was executed.
+    pub taken: u64,
+    /// How often the instruction was skipped.
+    pub not_taken: u64,
+}
+
+/// A location in the source code a program was compiled from.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: u32,
+}
+
+impl CoverageTracer {
+    pub fn new(initial_code_hash: U256) -> Self {
+        Self {
+            initial_code_hash,
+            programs: HashMap::new(),
+        }
+    }
+
+    pub fn programs(&self) -> &HashMap<U256, ProgramCoverage> {
+        &self.programs
+    }
+
+    /// Exports the coverage in the lcov tracefile format with one record per program.
+    /// The source file is the code hash and each instruction is a line, numbered from one.
+    pub fn to_lcov(&self) -> String {
+        let mut programs = self.programs.iter().collect::<Vec<_>>();
+        programs.sort_by_key(|(hash, _)| **hash);
+
+        let mut out = String::new();
+        for (hash, coverage) in programs {
+            let mut hash_bytes = [0; 32];
+            hash.to_big_endian(&mut hash_bytes);
+            let lines = coverage
+                .hits
+                .iter()
+                .enumerate()
+                .map(|(pc, hits)| {
+                    let branches = coverage.branches.get(&(pc as u16)).copied();
+                    (pc as u32 + 1, (*hits, branches.into_iter().collect()))
+                })
+                .collect();
+            write_record(&mut out, &format!("0x{}", hex(&hash_bytes)), &lines);
+        }
+        out
+    }
+
+    /// Exports the coverage in the lcov tracefile format using a mapping from code hash and pc to source lines.
+    ///
+    /// A line's hit count is the highest count of the instructions mapped to it.
+    /// Each predicated instruction on a line is a separate block of branches.
+    /// Instructions without a source line are left out.
+    pub fn to_lcov_with_source_map(
+        &self,
+        source_map: impl Fn(U256, u16) -> Option<SourceLine>,
+    ) -> String {
+        let mut files = BTreeMap::<String, BTreeMap<u32, (u64, Vec<BranchCoverage>)>>::new();
+
+        let mut programs = self.programs.iter().collect::<Vec<_>>();
+        programs.sort_by_key(|(hash, _)| **hash);
+        for (&hash, coverage) in programs {
+            for (pc, &hits) in coverage.hits.iter().enumerate() {
+                let Some(source) = source_map(hash, pc as u16) else {
+                    continue;
+                };
+                let line = files
+                    .entry(source.file)
+                    .or_default()
+                    .entry(source.line)
+                    .or_default();
+                line.0 = line.0.max(hits);
+                if let Some(branch) = coverage.branches.get(&(pc as u16)) {
+                    line.1.push(*branch);
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for (file, lines) in files {
+            write_record(&mut out, &file, &lines);
+        }
+        out
+    }
+}
+
+fn write_record(out: &mut String, file: &str, lines: &BTreeMap<u32, (u64, Vec<BranchCoverage>)>) {
+    writeln!(out, "SF:{file}").unwrap();
+
+    let (mut branches_found, mut branches_hit) = (0, 0);
+    for (line, (hits, branches)) in lines {
+        // Branches of lines that were never reached are reported as `-`.
+        let count = |n: u64| {
+            if *hits == 0 {
+                "-".to_string()
+            } else {
+                n.to_string()
+            }
+        };
+        for (block, branch) in branches.iter().enumerate() {
+            for (i, n) in [branch.taken, branch.not_taken].into_iter().enumerate() {
+                writeln!(out, "BRDA:{line},{block},{i},{}", count(n)).unwrap();
+                branches_found += 1;
+                if n != 0 {
+                    branches_hit += 1;
+                }
+            }
+        }
+    }
+    writeln!(out, "BRF:{branches_found}").unwrap();
+    writeln!(out, "BRH:{branches_hit}").unwrap();
+
+    for (line, (hits, _)) in lines {
+        writeln!(out, "DA:{line},{hits}").unwrap();
+    }
+    writeln!(out, "LF:{}", lines.len()).unwrap();
+    writeln!(
+        out,
+        "LH:{}",
+        lines.values().filter(|(hits, _)| *hits != 0).count()
+    )
+    .unwrap();
+    writeln!(out, "end_of_record").unwrap();
+}
+
+impl Tracer for CoverageTracer {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        let frame = &vm.state.current_frame;
+        let hash = frame.code_hash.unwrap_or(self.initial_code_hash);
+        let instructions = frame.program.instructions();
+        let coverage = self
+            .programs
+            .entry(hash)
+            .or_insert_with(|| ProgramCoverage {
+                hits: vec![0; instructions.len()],
+                branches: BTreeMap::new(),
+            });
+        coverage.hits[pc as usize] += 1;
+
+        let predicate = instructions[pc as usize].arguments.predicate;
+        if predicate != Predicate::Always {
+            let branch = coverage.branches.entry(pc).or_default();
+            if predicate.satisfied(&vm.state.flags) {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{ExecutionEnd, StorageWrite, Tracer, VirtualMachine};
+use std::{collections::BTreeSet, ops::Range};
+use u256::{H160, U256};
+
+/// Stops execution at breakpoints and after writes to watched locations.
+///
+/// Execution stops with [crate::ExecutionEnd::StoppedByTracer] and can be continued
+/// from the returned pc. [Debugger::last_hit] tells why execution stopped.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<(H160, u16)>,
+    storage_watchpoints: BTreeSet<(H160, U256)>,
+    transient_storage_watchpoints: BTreeSet<(H160, U256)>,
+    heap_watchpoints: Vec<(u32, Range<u32>)>,
+
+    /// A watchpoint that was hit during the current instruction.
+    /// Execution stops before the next one.
+    pending_hit: Option<Hit>,
+    last_hit: Option<Hit>,
+
+    /// Where execution last stopped, so that resuming there doesn't report [Debugger::last_hit] again.
+    stopped_at: Option<(H160, u16)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Hit {
+    Breakpoint {
+        code_address: H160,
+        pc: u16,
+    },
+    StorageWrite {
+        contract: H160,
+        key: U256,
+        value: U256,
+    },
+    TransientStorageWrite {
+        contract: H160,
+        key: U256,
+        value: U256,
+    },
+    HeapWrite {
+        heap: u32,
+        address: u32,
+        value: U256,
+    },
+}
+
+impl Debugger {
+    /// Stops before executing instruction `pc` of the code deployed at `code_address`.
+    /// For delegate calls this is the address the code comes from, not the one whose storage is used.
+    pub fn add_breakpoint(&mut self, code_address: H160, pc: u16) {
+        self.breakpoints.insert((code_address, pc));
+    }
+
+    pub fn remove_breakpoint(&mut self, code_address: H160, pc: u16) {
+        self.breakpoints.remove(&(code_address, pc));
+    }
+
+    /// Stops after every write to the storage slot.
+    pub fn watch_storage(&mut self, contract: H160, key: U256) {
+        self.storage_watchpoints.insert((contract, key));
+    }
+
+    pub fn unwatch_storage(&mut self, contract: H160, key: U256) {
+        self.storage_watchpoints.remove(&(contract, key));
+    }
+
+    /// Stops after every write to the transient storage slot.
+    pub fn watch_transient_storage(&mut self, contract: H160, key: U256) {
+        self.transient_storage_watchpoints.insert((contract, key));
+    }
+
+    pub fn unwatch_transient_storage(&mut self, contract: H160, key: U256) {
+        self.transient_storage_watchpoints.remove(&(contract, key));
+    }
+
+    /// Stops after every write that touches one of the bytes in `range` of `heap`,
+    /// whether it is made by a heap write instruction or by a precompile.
+    ///
+    /// Far calls and returns pass calldata and return data by pointer, so they never write to a heap.
+    pub fn watch_heap(&mut self, heap: u32, range: Range<u32>) {
+        self.heap_watchpoints.push((heap, range));
+    }
+
+    pub fn unwatch_heap(&mut self, heap: u32, range: Range<u32>) {
+        self.heap_watchpoints
+            .retain(|w| *w != (heap, range.clone()));
+    }
+
+    /// The reason execution was stopped the last time.
+    pub fn last_hit(&self) -> Option<&Hit> {
+        self.last_hit.as_ref()
+    }
+
+    fn hit_watchpoint(&mut self, hit: Hit) {
+        // If an instruction hits multiple watchpoints, the first one is reported.
+        self.pending_hit.get_or_insert(hit);
+    }
+}
+
+impl Tracer for Debugger {
+    fn should_stop(&mut self, vm: &VirtualMachine, pc: u16) -> bool {
+        let code_address = vm.state.current_frame.code_address;
+        let resumed_here = self.stopped_at.take() == Some((code_address, pc));
+
+        let hit = self.pending_hit.take().or_else(|| {
+            let breakpoint = Hit::Breakpoint { code_address, pc };
+            let already_reported = resumed_here && self.last_hit.as_ref() == Some(&breakpoint);
+            (self.breakpoints.contains(&(code_address, pc)) && !already_reported)
+                .then_some(breakpoint)
+        });
+
+        if hit.is_some() {
+            self.last_hit = hit;
+            self.stopped_at = Some((code_address, pc));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn on_execution_end(&mut self, _: &VirtualMachine, end: &ExecutionEnd) {
+        // Stopping is the only way execution ends that this tracer knows how to resume from.
+        if !matches!(end, ExecutionEnd::StoppedByTracer { .. }) {
+            self.pending_hit = None;
+            self.stopped_at = None;
+        }
+    }
+
+    fn on_storage_write(&mut self, _: &VirtualMachine, write: &StorageWrite) {
+        if self
+            .storage_watchpoints
+            .contains(&(write.contract, write.key))
+        {
+            self.hit_watchpoint(Hit::StorageWrite {
+                contract: write.contract,
+                key: write.key,
+                value: write.value,
+            });
+        }
+    }
+
+    fn on_transient_storage_write(
+        &mut self,
+        _: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        _: Option<U256>,
+        value: U256,
+    ) {
+        if self
+            .transient_storage_watchpoints
+            .contains(&(contract, key))
+        {
+            self.hit_watchpoint(Hit::TransientStorageWrite {
+                contract,
+                key,
+                value,
+            });
+        }
+    }
+
+    fn on_heap_write(&mut self, _: &VirtualMachine, heap: u32, address: u32, value: U256) {
+        let written = address..address + 32;
+        if self.heap_watchpoints.iter().any(|(watched_heap, range)| {
+            *watched_heap == heap && range.start < written.end && written.start < range.end
+        }) {
+            self.hit_watchpoint(Hit::HeapWrite {
+                heap,
+                address,
+                value,
+            });
+        }
+    }
+}
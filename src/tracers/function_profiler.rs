@@ -0,0 +1,119 @@
+use crate::{
+    instruction_handlers::{CallingMode, ReturnType},
+    Tracer, VirtualMachine,
+};
+use std::collections::HashMap;
+use u256::H160;
+
+/// Treats the destination of every near call as a function and measures how much gas each function uses.
+///
+/// A function's gas use is the gas passed to it minus the gas it returns. That includes
+/// the cost of the return instruction and the gas spent by far calls made from the function.
+#[derive(Default)]
+pub struct FunctionProfiler {
+    functions: HashMap<Function, FunctionStats>,
+
+    /// The active near calls of each far call frame, innermost last.
+    frames: Vec<Vec<ActiveCall>>,
+}
+
+/// A function is identified by the label its near calls jump to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Function {
+    pub code_address: H160,
+    pub label: u16,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FunctionStats {
+    pub calls: u64,
+    /// How many of the calls reverted or panicked.
+    pub failed_calls: u64,
+    pub gas_passed: u64,
+    pub gas_returned: u64,
+    /// The gas used by the function, including the functions it called.
+    /// Recursive calls are counted once for each level of recursion.
+    pub inclusive_gas: u64,
+    /// The gas used by the function, excluding the near calls it made.
+    pub exclusive_gas: u64,
+}
+
+struct ActiveCall {
+    function: Function,
+    gas_passed: u32,
+    gas_used_by_callees: u64,
+}
+
+impl FunctionProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn functions(&self) -> &HashMap<Function, FunctionStats> {
+        &self.functions
+    }
+
+    /// All functions that were called, ordered by their exclusive gas use, most expensive first.
+    pub fn by_exclusive_gas(&self) -> Vec<(Function, FunctionStats)> {
+        let mut functions = self
+            .functions
+            .iter()
+            .map(|(function, stats)| (*function, *stats))
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.exclusive_gas));
+        functions
+    }
+
+    fn current_frame(&mut self) -> &mut Vec<ActiveCall> {
+        if self.frames.is_empty() {
+            self.frames.push(vec![]);
+        }
+        self.frames.last_mut().unwrap()
+    }
+}
+
+impl Tracer for FunctionProfiler {
+    fn on_near_call(&mut self, vm: &VirtualMachine, destination: u16) {
+        let call = ActiveCall {
+            function: Function {
+                code_address: vm.state.current_frame.code_address,
+                label: destination,
+            },
+            gas_passed: vm.state.current_frame.gas,
+            gas_used_by_callees: 0,
+        };
+        self.current_frame().push(call);
+    }
+
+    fn on_near_return(&mut self, vm: &VirtualMachine, return_type: ReturnType) {
+        // Near calls made before the profiler was attached are ignored.
+        let Some(call) = self.current_frame().pop() else {
+            return;
+        };
+
+        let gas_returned = vm.state.current_frame.gas;
+        let gas_used = call.gas_passed.saturating_sub(gas_returned) as u64;
+        if let Some(caller) = self.current_frame().last_mut() {
+            caller.gas_used_by_callees += gas_used;
+        }
+
+        let stats = self.functions.entry(call.function).or_default();
+        stats.calls += 1;
+        if return_type.is_failure() {
+            stats.failed_calls += 1;
+        }
+        stats.gas_passed += call.gas_passed as u64;
+        stats.gas_returned += gas_returned as u64;
+        stats.inclusive_gas += gas_used;
+        stats.exclusive_gas += gas_used.saturating_sub(call.gas_used_by_callees);
+    }
+
+    fn on_far_call(&mut self, _: &VirtualMachine, _: CallingMode) {
+        self.current_frame();
+        self.frames.push(vec![]);
+    }
+
+    fn on_far_return(&mut self, _: &VirtualMachine, _: ReturnType, _: &[u8]) {
+        self.frames.pop();
+    }
+}
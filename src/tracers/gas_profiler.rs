@@ -0,0 +1,172 @@
+use crate::{
+    instruction_handlers::{CallingMode, ReturnType},
+    PanicReason, Tracer, VirtualMachine,
+};
+use std::{collections::HashMap, fmt::Write};
+use u256::H160;
+
+/// Attributes the gas spent by each instruction to the stack of far calls it was executed in.
+///
+/// Gas passed to a far call is only counted as spent once the callee spends it, so the leftover gas
+/// returned to the caller is not spent. Memory growth and decommit costs of a far call are
+/// attributed to the far call instruction, the memory growth of a return to the return instruction.
+#[derive(Default)]
+pub struct GasProfiler {
+    /// Every distinct stack seen so far, stored as a tree.
+    nodes: Vec<Node>,
+    roots: HashMap<StackFrame, usize>,
+
+    /// The far call instructions that lead to the current frame, innermost last.
+    callers: Vec<usize>,
+    /// The last instruction that started and the gas left when it was last charged.
+    current: Option<(usize, u64)>,
+}
+
+/// Code in the stack of a [GasProfiler]. For callers, `pc` is the far call instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct StackFrame {
+    /// Delegate calls are attributed to the code that is executed, not the contract they are made on behalf of.
+    pub code_address: H160,
+    pub pc: u16,
+}
+
+/// The gas used by the innermost instruction of a stack.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct GasUsage {
+    /// Refunds can make this negative.
+    pub spent: i64,
+    /// For far calls, the gas passed to the callees, not including stipends.
+    pub passed: u64,
+    /// For far calls, the gas the callees returned.
+    /// [GasUsage::passed] minus this is the gas spent by the callees.
+    pub returned: u64,
+}
+
+struct Node {
+    frame: StackFrame,
+    parent: Option<usize>,
+    children: HashMap<StackFrame, usize>,
+    usage: GasUsage,
+}
+
+impl GasProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All stacks that were executed, outermost frame first.
+    pub fn stacks(&self) -> Vec<(Vec<StackFrame>, GasUsage)> {
+        (0..self.nodes.len())
+            .map(|node| (self.stack(node), self.nodes[node].usage))
+            .collect()
+    }
+
+    /// Formats the profile in the folded stacks format read by `flamegraph.pl` and `inferno`.
+    /// Each line is a stack of `address:pc` pairs separated by `;`, followed by the gas spent in it.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines = self
+            .stacks()
+            .into_iter()
+            .filter(|(_, usage)| usage.spent > 0)
+            .map(|(stack, usage)| {
+                let mut line = String::new();
+                for (i, frame) in stack.iter().enumerate() {
+                    if i != 0 {
+                        line.push(';');
+                    }
+                    write!(line, "{:#x}:{}", frame.code_address, frame.pc).unwrap();
+                }
+                write!(line, " {}", usage.spent).unwrap();
+                line
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+
+    fn stack(&self, mut node: usize) -> Vec<StackFrame> {
+        let mut stack = vec![self.nodes[node].frame];
+        while let Some(parent) = self.nodes[node].parent {
+            stack.push(self.nodes[parent].frame);
+            node = parent;
+        }
+        stack.reverse();
+        stack
+    }
+
+    fn charge_current_instruction(&mut self, vm: &VirtualMachine) {
+        if let Some((node, gas_before)) = &mut self.current {
+            let gas = vm.state.total_gas_without_stipends();
+            self.nodes[*node].usage.spent += *gas_before as i64 - gas as i64;
+            *gas_before = gas;
+        }
+    }
+
+    fn node(&mut self, parent: Option<usize>, frame: StackFrame) -> usize {
+        let new_node = self.nodes.len();
+        let children = match parent {
+            Some(parent) => &mut self.nodes[parent].children,
+            None => &mut self.roots,
+        };
+        let node = *children.entry(frame).or_insert(new_node);
+        if node != new_node {
+            return node;
+        }
+
+        self.nodes.push(Node {
+            frame,
+            parent,
+            children: HashMap::new(),
+            usage: GasUsage::default(),
+        });
+        node
+    }
+}
+
+impl Tracer for GasProfiler {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        // Gas spent between instructions belongs to the previous one.
+        // That happens when an instruction panics, as the panic is executed separately.
+        self.charge_current_instruction(vm);
+
+        let frame = StackFrame {
+            code_address: vm.state.current_frame.code_address,
+            pc,
+        };
+        let node = self.node(self.callers.last().copied(), frame);
+        self.current = Some((node, vm.state.total_gas_without_stipends()));
+    }
+
+    fn after_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        self.charge_current_instruction(vm);
+    }
+
+    fn on_far_call(&mut self, vm: &VirtualMachine, _: CallingMode) {
+        if let Some((node, _)) = self.current {
+            let frame = &vm.state.current_frame;
+            self.nodes[node].usage.passed += (frame.gas - frame.stipend) as u64;
+            self.callers.push(node);
+        }
+    }
+
+    fn on_failed_far_call(
+        &mut self,
+        _: &VirtualMachine,
+        _: CallingMode,
+        _: H160,
+        gas: u32,
+        _: PanicReason,
+    ) {
+        if let Some((node, _)) = self.current {
+            self.nodes[node].usage.passed += gas as u64;
+        }
+    }
+
+    fn on_far_return(&mut self, vm: &VirtualMachine, _: ReturnType, _: &[u8]) {
+        if let Some(node) = self.callers.pop() {
+            let frame = &vm.state.current_frame;
+            self.nodes[node].usage.returned += frame.gas.saturating_sub(frame.stipend) as u64;
+        }
+    }
+}
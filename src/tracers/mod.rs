@@ -0,0 +1,27 @@
+//! Ready-made [crate::Tracer]s.
+
+pub use call_tree::{write_json_string, Call, CallOutcome, CallTracer};
+pub use circuits::{CircuitEstimator, CircuitUsage};
+pub use coverage::{BranchCoverage, CoverageTracer, ProgramCoverage, SourceLine};
+pub use debugger::{Debugger, Hit};
+pub use function_profiler::{Function, FunctionProfiler, FunctionStats};
+pub use gas_profiler::{GasProfiler, GasUsage, StackFrame};
+pub use opcode_stats::{OpcodeProfiler, OpcodeReport, OpcodeStats};
+pub use reverts::{Revert, RevertCollector};
+pub use step_recorder::{HeapWrite, StepRecord, StepRecorder};
+pub use storage_trace::{StorageAccess, StorageAccessKind, StorageTracer};
+pub use struct_log::StructLogger;
+pub use witness::{WitnessQueries, WitnessRecorder};
+
+mod call_tree;
+mod circuits;
+mod coverage;
+mod debugger;
+mod function_profiler;
+mod gas_profiler;
+mod opcode_stats;
+mod reverts;
+mod step_recorder;
+mod storage_trace;
+mod struct_log;
+mod witness;
@@ -0,0 +1,108 @@
+use crate::{Predicate, Tracer, VirtualMachine};
+use std::collections::HashMap;
+use zkevm_opcode_defs::Opcode;
+
+/// Counts how often each opcode was executed and how much gas it cost.
+#[derive(Default)]
+pub struct OpcodeProfiler {
+    report: OpcodeReport,
+
+    /// The opcode being executed and the gas that should be left once it has paid its static cost.
+    current: Option<(Opcode, u64)>,
+    /// The last opcode executed and the gas left after it.
+    /// Gas spent between instructions, for example by a panic, is attributed to it.
+    last: Option<(Opcode, u64)>,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct OpcodeReport {
+    pub opcodes: HashMap<Opcode, OpcodeStats>,
+    /// How many instructions were skipped because of their predicate.
+    pub skipped_by_predicate: HashMap<Predicate, u64>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct OpcodeStats {
+    pub executed: u64,
+    /// Skipped instructions pay their static cost but don't count as executed.
+    pub skipped: u64,
+    pub static_gas: u64,
+    /// Gas spent on top of the static cost, for example on memory growth, decommits or precompiles.
+    /// Storage refunds are subtracted, so this can be negative.
+    ///
+    /// Gas passed to another frame is not spent until that frame spends it,
+    /// so it only counts if a far call fails before the callee is created.
+    pub dynamic_gas: i64,
+}
+
+impl OpcodeProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> &OpcodeReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> OpcodeReport {
+        self.report
+    }
+}
+
+impl OpcodeStats {
+    pub fn total_gas(&self) -> i64 {
+        self.static_gas as i64 + self.dynamic_gas
+    }
+}
+
+impl OpcodeReport {
+    /// All opcodes that were encountered, the most expensive first.
+    pub fn by_total_gas(&self) -> Vec<(Opcode, OpcodeStats)> {
+        let mut opcodes = self
+            .opcodes
+            .iter()
+            .map(|(opcode, stats)| (*opcode, *stats))
+            .collect::<Vec<_>>();
+        opcodes.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total_gas()));
+        opcodes
+    }
+}
+
+impl Tracer for OpcodeProfiler {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        let gas = vm.state.total_gas_without_stipends();
+        if let Some((opcode, gas_after)) = self.last.take() {
+            self.report.opcodes.entry(opcode).or_default().dynamic_gas +=
+                gas_after as i64 - gas as i64;
+        }
+
+        let instruction = &vm.state.current_frame.program.instructions()[pc as usize];
+        let args = &instruction.arguments;
+        // If the frame cannot pay the static cost, it loses all of its gas instead.
+        let static_gas = args.get_static_gas_cost().min(vm.state.current_frame.gas) as u64;
+
+        let stats = self.report.opcodes.entry(instruction.opcode).or_default();
+        stats.static_gas += static_gas;
+        if args.predicate.satisfied(&vm.state.flags) {
+            stats.executed += 1;
+        } else {
+            stats.skipped += 1;
+            *self
+                .report
+                .skipped_by_predicate
+                .entry(args.predicate)
+                .or_default() += 1;
+        }
+
+        self.current = Some((instruction.opcode, gas - static_gas));
+    }
+
+    fn after_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        if let Some((opcode, expected_gas)) = self.current.take() {
+            let gas = vm.state.total_gas_without_stipends();
+            self.report.opcodes.entry(opcode).or_default().dynamic_gas +=
+                expected_gas as i64 - gas as i64;
+            self.last = Some((opcode, gas));
+        }
+    }
+}
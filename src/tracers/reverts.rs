@@ -0,0 +1,49 @@
+use crate::{
+    instruction_handlers::ReturnType, ErrorRegistry, RevertReason, Tracer, VirtualMachine,
+};
+use u256::H160;
+
+/// Decodes the revert reason of every far call that reverts,
+/// including the ones that are caught by their caller.
+#[derive(Default)]
+pub struct RevertCollector {
+    registry: ErrorRegistry,
+    reverts: Vec<Revert>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Revert {
+    pub address: H160,
+    pub code_address: H160,
+    /// The number of far calls the reverting frame was nested in, so zero for the initial frame.
+    pub depth: usize,
+    pub reason: RevertReason,
+}
+
+impl RevertCollector {
+    pub fn new(registry: ErrorRegistry) -> Self {
+        Self {
+            registry,
+            reverts: vec![],
+        }
+    }
+
+    /// All reverts in the order they happened.
+    pub fn reverts(&self) -> &[Revert] {
+        &self.reverts
+    }
+}
+
+impl Tracer for RevertCollector {
+    fn on_far_return(&mut self, vm: &VirtualMachine, return_type: ReturnType, return_data: &[u8]) {
+        if return_type == ReturnType::Revert {
+            let frame = &vm.state.current_frame;
+            self.reverts.push(Revert {
+                address: frame.address,
+                code_address: frame.code_address,
+                depth: vm.state.previous_frames.len(),
+                reason: RevertReason::decode(return_data, &self.registry),
+            });
+        }
+    }
+}
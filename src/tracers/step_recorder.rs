@@ -0,0 +1,99 @@
+use crate::{StorageWrite, Tracer, VirtualMachine};
+use u256::{H160, U256};
+
+/// Records what every instruction did, so that two executions can be compared with [crate::compare_traces].
+#[derive(Default)]
+pub struct StepRecorder {
+    steps: Vec<StepRecord>,
+    current: Option<StepRecord>,
+}
+
+/// An executed instruction and its effects.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StepRecord {
+    pub pc: u16,
+    /// The number of far calls the instruction's frame is nested in.
+    pub frame: usize,
+    pub code_address: H160,
+    pub mnemonic: &'static str,
+
+    /// The registers and flags after the instruction.
+    pub registers: [U256; 16],
+    pub register_pointer_flags: u16,
+    /// Less than or overflow, equal and greater than.
+    pub flags: [bool; 3],
+
+    pub heap_writes: Vec<HeapWrite>,
+    pub storage_writes: Vec<StorageWrite>,
+}
+
+/// A 32-byte big-endian word written to a heap.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HeapWrite {
+    pub heap: u32,
+    pub address: u32,
+    pub value: U256,
+}
+
+impl StepRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> &[StepRecord] {
+        &self.steps
+    }
+
+    pub fn into_steps(self) -> Vec<StepRecord> {
+        self.steps
+    }
+
+    /// Removes and returns the steps recorded so far.
+    pub(crate) fn take_steps(&mut self) -> Vec<StepRecord> {
+        std::mem::take(&mut self.steps)
+    }
+}
+
+impl Tracer for StepRecorder {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        let frame = &vm.state.current_frame;
+        self.current = Some(StepRecord {
+            pc,
+            frame: vm.state.previous_frames.len(),
+            code_address: frame.code_address,
+            mnemonic: frame.program.instructions()[pc as usize].mnemonic(),
+            registers: Default::default(),
+            register_pointer_flags: 0,
+            flags: [false; 3],
+            heap_writes: vec![],
+            storage_writes: vec![],
+        });
+    }
+
+    fn after_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        let Some(mut step) = self.current.take() else {
+            return;
+        };
+        let state = &vm.state;
+        step.registers = state.registers;
+        step.register_pointer_flags = state.register_pointer_flags;
+        step.flags = [state.flags.lt_of(), state.flags.eq(), state.flags.gt()];
+        self.steps.push(step);
+    }
+
+    fn on_heap_write(&mut self, _: &VirtualMachine, heap: u32, address: u32, value: U256) {
+        if let Some(step) = &mut self.current {
+            step.heap_writes.push(HeapWrite {
+                heap,
+                address,
+                value,
+            });
+        }
+    }
+
+    fn on_storage_write(&mut self, _: &VirtualMachine, write: &StorageWrite) {
+        if let Some(step) = &mut self.current {
+            step.storage_writes.push(*write);
+        }
+    }
+}
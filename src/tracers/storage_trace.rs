@@ -0,0 +1,177 @@
+use crate::{
+    instruction_handlers::{CallingMode, ReturnType},
+    StorageWrite, Tracer, VirtualMachine,
+};
+use u256::{H160, U256};
+
+/// Records every access to persistent and transient storage.
+#[derive(Default)]
+pub struct StorageTracer {
+    accesses: Vec<StorageAccess>,
+
+    /// The index of the first access made by each active near or far call.
+    frame_starts: Vec<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StorageAccess {
+    pub kind: StorageAccessKind,
+    pub contract: H160,
+    pub key: U256,
+    /// Same as [StorageAccess::value] for reads.
+    pub previous_value: U256,
+    /// The value read or written.
+    pub value: U256,
+    /// Whether the slot had already been accessed, or written for writes. Transient storage is always warm.
+    pub warm: bool,
+    pub refund: u32,
+    pub pubdata_change: i32,
+    /// True if the frame that made the access reverted or panicked, or one of the frames it was called from.
+    /// Failure of the initial frame doesn't count, as the VM leaves rolling that back to its caller.
+    pub rolled_back: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StorageAccessKind {
+    Read,
+    Write,
+    TransientRead,
+    TransientWrite,
+}
+
+impl StorageTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All accesses in the order they were made.
+    pub fn accesses(&self) -> &[StorageAccess] {
+        &self.accesses
+    }
+
+    pub fn into_accesses(self) -> Vec<StorageAccess> {
+        self.accesses
+    }
+
+    fn frame_returned(&mut self, return_type: ReturnType) {
+        if let Some(start) = self.frame_starts.pop() {
+            if return_type.is_failure() {
+                for access in &mut self.accesses[start..] {
+                    access.rolled_back = true;
+                }
+            }
+        }
+    }
+
+    fn record_transient(
+        &mut self,
+        kind: StorageAccessKind,
+        contract: H160,
+        key: U256,
+        previous_value: U256,
+        value: U256,
+    ) {
+        self.accesses.push(StorageAccess {
+            kind,
+            contract,
+            key,
+            previous_value,
+            value,
+            warm: true,
+            refund: 0,
+            pubdata_change: 0,
+            rolled_back: false,
+        });
+    }
+}
+
+impl Tracer for StorageTracer {
+    fn on_storage_read(
+        &mut self,
+        _: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        value: U256,
+        refund: u32,
+        warm: bool,
+    ) {
+        self.accesses.push(StorageAccess {
+            kind: StorageAccessKind::Read,
+            contract,
+            key,
+            previous_value: value,
+            value,
+            warm,
+            refund,
+            pubdata_change: 0,
+            rolled_back: false,
+        });
+    }
+
+    fn on_storage_write(&mut self, _: &VirtualMachine, write: &StorageWrite) {
+        self.accesses.push(StorageAccess {
+            kind: StorageAccessKind::Write,
+            contract: write.contract,
+            key: write.key,
+            previous_value: write.previous_value.unwrap_or_default(),
+            value: write.value,
+            warm: write.warm,
+            refund: write.refund,
+            pubdata_change: write.pubdata_change,
+            rolled_back: false,
+        });
+    }
+
+    fn wants_previous_storage_values(&self) -> bool {
+        true
+    }
+
+    fn on_transient_storage_read(
+        &mut self,
+        _: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        value: U256,
+    ) {
+        self.record_transient(
+            StorageAccessKind::TransientRead,
+            contract,
+            key,
+            value,
+            value,
+        );
+    }
+
+    fn on_transient_storage_write(
+        &mut self,
+        _: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        previous_value: Option<U256>,
+        value: U256,
+    ) {
+        self.record_transient(
+            StorageAccessKind::TransientWrite,
+            contract,
+            key,
+            previous_value.unwrap_or_default(),
+            value,
+        );
+    }
+
+    fn on_near_call(&mut self, _: &VirtualMachine, _: u16) {
+        self.frame_starts.push(self.accesses.len());
+    }
+
+    fn on_near_return(&mut self, _: &VirtualMachine, return_type: ReturnType) {
+        self.frame_returned(return_type);
+    }
+
+    fn on_far_call(&mut self, _: &VirtualMachine, _: CallingMode) {
+        self.frame_starts.push(self.accesses.len());
+    }
+
+    fn on_far_return(&mut self, _: &VirtualMachine, return_type: ReturnType, _: &[u8]) {
+        self.frame_returned(return_type);
+    }
+}
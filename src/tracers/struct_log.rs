@@ -0,0 +1,85 @@
+use crate::{Tracer, VirtualMachine};
+use std::io::{self, Write};
+
+/// Writes one JSON object per executed instruction, in the spirit of geth's struct logger.
+///
+/// Each line looks like this, with registers and flags as they were before the instruction:
+/// `{"pc":3,"op":"add","gas":9950,"gasCost":6,"depth":1,"nearCallDepth":0,"registers":[{"value":"0x0","isPointer":false},...],"flags":{"lt":false,"eq":false,"gt":true}}`
+///
+/// `depth` starts at one for the initial frame, like in geth. `gasCost` includes
+/// the dynamic costs of the instruction but not gas passed to other frames.
+pub struct StructLogger<W: Write> {
+    out: W,
+    /// The line for the instruction being executed, which is missing the cost,
+    /// split where the cost goes. Also the gas left before the instruction.
+    pending: Option<(String, String, u64)>,
+    /// Writing stops after the first error, which is reported by [StructLogger::finish].
+    error: Option<io::Error>,
+}
+
+impl<W: Write> StructLogger<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            pending: None,
+            error: None,
+        }
+    }
+
+    /// Returns the writer or the first error that happened while writing to it.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Tracer for StructLogger<W> {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let state = &vm.state;
+        let instruction = &state.current_frame.program.instructions()[pc as usize];
+        let registers = state
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                format!(
+                    r#"{{"value":"{value:#x}","isPointer":{}}}"#,
+                    state.register_pointer_flags & (1 << i) != 0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let before_cost = format!(
+            r#"{{"pc":{pc},"op":"{}","gas":{},"gasCost":"#,
+            instruction.mnemonic(),
+            state.current_frame.gas,
+        );
+        let after_cost = format!(
+            r#","depth":{},"nearCallDepth":{},"registers":[{registers}],"flags":{{"lt":{},"eq":{},"gt":{}}}}}"#,
+            state.previous_frames.len() + 1,
+            state.current_frame.near_call_sites().len(),
+            state.flags.lt_of(),
+            state.flags.eq(),
+            state.flags.gt(),
+        );
+        self.pending = Some((before_cost, after_cost, state.total_gas_without_stipends()));
+    }
+
+    fn after_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        let Some((before_cost, after_cost, gas_before)) = self.pending.take() else {
+            return;
+        };
+        let cost = gas_before as i64 - vm.state.total_gas_without_stipends() as i64;
+        if let Err(error) = writeln!(self.out, "{before_cost}{cost}{after_cost}") {
+            self.error = Some(error);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::{
+    instruction_handlers::{CallingMode, ReturnType},
+    Event, L2ToL1Log, PrecompileCall, StorageWrite, Tracer, VirtualMachine,
+};
+use u256::{H160, U256};
+use zk_evm_abstractions::{
+    aux::{MemoryIndex, MemoryLocation, MemoryPage, MemoryType},
+    queries::{
+        DecommittmentQuery, LogQuery, MemoryQuery, VersionedHashHeader,
+        VersionedHashNormalizedPreimage,
+    },
+};
+use zkevm_opcode_defs::{
+    LogOpcode, Opcode, UMAOpcode, EVENT_AUX_BYTE, L1_MESSAGE_AUX_BYTE, STORAGE_AUX_BYTE,
+    TRANSIENT_STORAGE_AUX_BYTE,
+};
+
+/// Records the queries the reference VM emits, so that the same auxiliary data
+/// can be produced for proving.
+///
+/// Queries are recorded in the order they are made and carry the timestamps of [crate::State].
+/// Each query is paired with the [crate::State::monotonic_cycle_counter] of the instruction that made it.
+#[derive(Default)]
+pub struct WitnessRecorder {
+    queries: WitnessQueries,
+
+    /// Whether the current instruction reads through a fat pointer.
+    reading_fat_pointer: bool,
+    /// Precompiles report their writes through [Tracer::on_precompile_call] too.
+    calling_precompile: bool,
+    /// Writes, events and L2 to L1 logs that are rolled back if a frame fails.
+    rollbackable: Vec<LogQuery>,
+    /// The index in `rollbackable` at which each active near or far call starts.
+    frame_starts: Vec<usize>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct WitnessQueries {
+    /// Heap reads and writes, including those made by precompiles.
+    /// An access that isn't aligned to 32 bytes touches two words and is recorded as two queries.
+    pub memory: Vec<(u32, MemoryQuery)>,
+    /// Storage accesses, events and L2 to L1 logs.
+    /// When a near or far call fails, its writes, events and logs are repeated
+    /// in reverse order with the `rollback` flag set, paired with the cycle of the return.
+    pub log: Vec<(u32, LogQuery)>,
+    /// The VM doesn't load code into memory, so `memory_page` is always zero.
+    pub decommittment: Vec<(u32, DecommittmentQuery)>,
+    pub precompile: Vec<(u32, LogQuery)>,
+}
+
+impl WitnessRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn queries(&self) -> &WitnessQueries {
+        &self.queries
+    }
+
+    pub fn into_queries(self) -> WitnessQueries {
+        self.queries
+    }
+
+    fn record_memory(
+        &mut self,
+        vm: &VirtualMachine,
+        heap: u32,
+        address: u32,
+        memory_type: MemoryType,
+        rw_flag: bool,
+    ) {
+        let timestamp = if rw_flag {
+            vm.state.timestamp_for_second_decommit_or_precompile_write()
+        } else {
+            vm.state.timestamp_for_first_decommit_or_precompile_read()
+        };
+        let contents = vm.state.heaps.get(heap).unwrap_or_default();
+        let first_word = address / 32;
+        let last_word = (address as u64).div_ceil(32);
+        for index in first_word as u64..=last_word {
+            let start = index as usize * 32;
+            let mut word = [0; 32];
+            for (i, byte) in word.iter_mut().enumerate() {
+                *byte = contents.get(start + i).copied().unwrap_or(0);
+            }
+            self.queries.memory.push((
+                vm.state.monotonic_cycle_counter,
+                MemoryQuery {
+                    timestamp,
+                    location: MemoryLocation {
+                        memory_type,
+                        page: MemoryPage(heap),
+                        index: MemoryIndex(index as u32),
+                    },
+                    value: U256::from_big_endian(&word),
+                    rw_flag,
+                    value_is_pointer: false,
+                },
+            ));
+        }
+    }
+
+    fn record_log(&mut self, vm: &VirtualMachine, query: LogQuery) {
+        self.queries
+            .log
+            .push((vm.state.monotonic_cycle_counter, query));
+        if query.rw_flag {
+            self.rollbackable.push(query);
+        }
+    }
+
+    fn frame_returned(&mut self, vm: &VirtualMachine, return_type: ReturnType) {
+        if let Some(start) = self.frame_starts.pop() {
+            if return_type.is_failure() {
+                for mut query in self.rollbackable.drain(start..).rev() {
+                    query.rollback = true;
+                    self.queries
+                        .log
+                        .push((vm.state.monotonic_cycle_counter, query));
+                }
+            }
+        }
+    }
+}
+
+fn log_query(
+    vm: &VirtualMachine,
+    aux_byte: u8,
+    address: H160,
+    key: U256,
+    read_value: U256,
+    written_value: U256,
+    rw_flag: bool,
+) -> LogQuery {
+    LogQuery {
+        timestamp: vm.state.timestamp_for_first_decommit_or_precompile_read(),
+        tx_number_in_block: vm.state.transaction_number,
+        aux_byte,
+        shard_id: 0,
+        address,
+        key,
+        read_value,
+        written_value,
+        rw_flag,
+        rollback: false,
+        is_service: false,
+    }
+}
+
+impl Tracer for WitnessRecorder {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        let opcode = vm.state.current_frame.program.instructions()[pc as usize].opcode();
+        self.reading_fat_pointer = opcode == Opcode::UMA(UMAOpcode::FatPointerRead);
+        self.calling_precompile = opcode == Opcode::Log(LogOpcode::PrecompileCall);
+    }
+
+    fn on_heap_read(&mut self, vm: &VirtualMachine, heap: u32, address: u32, _: U256) {
+        let memory_type = if self.reading_fat_pointer {
+            MemoryType::FatPointer
+        } else if heap == vm.state.current_frame.aux_heap {
+            MemoryType::AuxHeap
+        } else {
+            MemoryType::Heap
+        };
+        self.record_memory(vm, heap, address, memory_type, false);
+    }
+
+    fn on_heap_write(&mut self, vm: &VirtualMachine, heap: u32, address: u32, _: U256) {
+        if self.calling_precompile {
+            return;
+        }
+        let memory_type = if heap == vm.state.current_frame.aux_heap {
+            MemoryType::AuxHeap
+        } else {
+            MemoryType::Heap
+        };
+        self.record_memory(vm, heap, address, memory_type, true);
+    }
+
+    fn on_decommit(&mut self, vm: &VirtualMachine, code_hash: U256, is_fresh: bool) {
+        let mut hash = [0; 32];
+        code_hash.to_big_endian(&mut hash);
+        self.queries.decommittment.push((
+            vm.state.monotonic_cycle_counter,
+            DecommittmentQuery {
+                header: VersionedHashHeader {
+                    data: hash[..4].try_into().unwrap(),
+                },
+                normalized_preimage: VersionedHashNormalizedPreimage {
+                    data: hash[4..].try_into().unwrap(),
+                },
+                timestamp: vm.state.timestamp_for_first_decommit_or_precompile_read(),
+                memory_page: MemoryPage(0),
+                decommitted_length: u16::from_be_bytes([hash[2], hash[3]]),
+                is_fresh,
+            },
+        ));
+    }
+
+    fn on_storage_read(
+        &mut self,
+        vm: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        value: U256,
+        _: u32,
+        _: bool,
+    ) {
+        let query = log_query(
+            vm,
+            STORAGE_AUX_BYTE,
+            contract,
+            key,
+            value,
+            U256::zero(),
+            false,
+        );
+        self.record_log(vm, query);
+    }
+
+    fn on_storage_write(&mut self, vm: &VirtualMachine, write: &StorageWrite) {
+        let query = log_query(
+            vm,
+            STORAGE_AUX_BYTE,
+            write.contract,
+            write.key,
+            write.previous_value.unwrap_or_default(),
+            write.value,
+            true,
+        );
+        self.record_log(vm, query);
+    }
+
+    fn wants_previous_storage_values(&self) -> bool {
+        true
+    }
+
+    fn on_transient_storage_read(
+        &mut self,
+        vm: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        value: U256,
+    ) {
+        let query = log_query(
+            vm,
+            TRANSIENT_STORAGE_AUX_BYTE,
+            contract,
+            key,
+            value,
+            U256::zero(),
+            false,
+        );
+        self.record_log(vm, query);
+    }
+
+    fn on_transient_storage_write(
+        &mut self,
+        vm: &VirtualMachine,
+        contract: H160,
+        key: U256,
+        previous_value: Option<U256>,
+        value: U256,
+    ) {
+        let query = log_query(
+            vm,
+            TRANSIENT_STORAGE_AUX_BYTE,
+            contract,
+            key,
+            previous_value.unwrap_or_default(),
+            value,
+            true,
+        );
+        self.record_log(vm, query);
+    }
+
+    fn on_event(&mut self, vm: &VirtualMachine, event: &Event) {
+        let query = LogQuery {
+            shard_id: event.shard_id,
+            tx_number_in_block: event.tx_number,
+            // The reference VM marks the first part of an event this way.
+            is_service: event.is_first,
+            ..log_query(
+                vm,
+                EVENT_AUX_BYTE,
+                vm.state.current_frame.address,
+                event.key,
+                U256::zero(),
+                event.value,
+                true,
+            )
+        };
+        self.record_log(vm, query);
+    }
+
+    fn on_l2_to_l1_log(&mut self, vm: &VirtualMachine, log: &L2ToL1Log) {
+        let query = LogQuery {
+            shard_id: log.shard_id,
+            tx_number_in_block: log.tx_number,
+            is_service: log.is_service,
+            ..log_query(
+                vm,
+                L1_MESSAGE_AUX_BYTE,
+                log.address,
+                log.key,
+                U256::zero(),
+                log.value,
+                true,
+            )
+        };
+        self.record_log(vm, query);
+    }
+
+    fn on_precompile_call(&mut self, vm: &VirtualMachine, call: &PrecompileCall) {
+        self.queries
+            .precompile
+            .push((vm.state.monotonic_cycle_counter, call.query));
+        self.queries
+            .memory
+            .extend(call.memory_reads.iter().chain(&call.memory_writes));
+    }
+
+    fn wants_precompile_memory_queries(&self) -> bool {
+        true
+    }
+
+    fn on_near_call(&mut self, _: &VirtualMachine, _: u16) {
+        self.frame_starts.push(self.rollbackable.len());
+    }
+
+    fn on_near_return(&mut self, vm: &VirtualMachine, return_type: ReturnType) {
+        self.frame_returned(vm, return_type);
+    }
+
+    fn on_far_call(&mut self, _: &VirtualMachine, _: CallingMode) {
+        self.frame_starts.push(self.rollbackable.len());
+    }
+
+    fn on_far_return(&mut self, vm: &VirtualMachine, return_type: ReturnType, _: &[u8]) {
+        self.frame_returned(vm, return_type);
+    }
+}
@@ -1,24 +1,57 @@
 use crate::modified_world::ExternalSnapshot;
 use crate::{
+    addressing_modes::INVALID_INSTRUCTION_COST,
+    backtrace::Backtrace,
     callframe::{Callframe, FrameRemnant},
     decommit::u256_into_address,
+    instruction::InstructionResult,
     instruction_handlers::{free_panic, CallingMode},
     modified_world::{Snapshot, WorldDiff},
     stack::StackPool,
-    state::State,
-    ExecutionEnd, Instruction, Program, World,
+    state::{Heaps, State},
+    ExecutionEnd, Instruction, PanicReason, Program, Tracer, World,
 };
-use u256::H160;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use u256::{H160, U256};
 use zkevm_opcode_defs::system_params::NEW_FRAME_MEMORY_STIPEND;
 
+/// Defaults to zeroed code hashes, hook address zero and no backtraces.
+#[derive(Default)]
 pub struct Settings {
     pub default_aa_code_hash: [u8; 32],
     pub evm_interpreter_code_hash: [u8; 32],
 
-    /// Writing to this address in the bootloader's heap suspends execution
+    /// Writing to this address in the bootloader's heap suspends execution,
+    /// unless a handler is registered with [VirtualMachine::set_hook_handler].
     pub hook_address: u32,
+
+    /// Whether to record [VirtualMachine::failure_backtrace], which makes reverts and panics slower.
+    pub record_failure_backtraces: bool,
 }
 
+/// What to do after a hook handler has run.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HookResult {
+    /// Keep executing the bootloader.
+    Continue,
+    /// End execution with [ExecutionEnd::SuspendedOnHook], as if there was no handler.
+    Suspend,
+    /// End execution with [ExecutionEnd::AbortedByHook].
+    Abort,
+}
+
+/// Called when the bootloader triggers the hook it is registered for.
+/// The hook's write to the heap has already happened.
+/// The handler may change the VM freely, including rolling it back.
+/// Execution continues after the index of the hook's write in the frame that is current when it returns.
+pub type HookHandler = Box<dyn FnMut(&mut VirtualMachine, &mut dyn World) -> HookResult + Send>;
+
 pub struct VirtualMachine {
     pub world_diff: WorldDiff,
 
@@ -29,8 +62,24 @@ pub struct VirtualMachine {
     pub(crate) settings: Settings,
 
     pub(crate) stack_pool: StackPool,
+
+    /// Set by code that causes a panic and taken by the return that carries it out,
+    /// along with the instruction that caused the panic.
+    pub(crate) pending_panic: Option<(PanicReason, u16)>,
+
+    pub(crate) failure_backtrace: Option<Backtrace>,
+
+    interrupt_flag: Option<Arc<AtomicBool>>,
+    instructions_until_interrupt_check: u32,
+
+    hook_handlers: HashMap<u32, HookHandler>,
+    /// The hook whose handler is running and whether that handler has been removed since.
+    running_hook: Option<(u32, bool)>,
 }
 
+/// Reading the interrupt flag is cheap but not free, so it is only read every this many instructions.
+const INTERRUPT_CHECK_INTERVAL: u32 = 1024;
+
 impl VirtualMachine {
     pub fn new(
         address: H160,
@@ -57,53 +106,109 @@ impl VirtualMachine {
             ),
             settings,
             stack_pool,
+            pending_panic: None,
+            failure_backtrace: None,
+            interrupt_flag: None,
+            instructions_until_interrupt_check: INTERRUPT_CHECK_INTERVAL,
+            hook_handlers: HashMap::new(),
+            running_hook: None,
         }
     }
 
-    pub fn run(&mut self, world: &mut dyn World) -> ExecutionEnd {
-        self.resume_from(0, world)
+    /// Execution ends with [ExecutionEnd::Interrupted] shortly after `flag` is set,
+    /// which allows stopping the VM from another thread.
+    ///
+    /// The flag is not cleared by the VM. It has to be cleared before resuming.
+    /// Setting it from a hook handler only takes effect once execution is resumed.
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt_flag = Some(flag);
+    }
+
+    /// Makes the bootloader call `handler` instead of suspending when it triggers `hook`.
+    /// Replaces the previous handler for `hook`, if any.
+    pub fn set_hook_handler(
+        &mut self,
+        hook: u32,
+        handler: impl FnMut(&mut VirtualMachine, &mut dyn World) -> HookResult + Send + 'static,
+    ) {
+        self.hook_handlers.insert(hook, Box::new(handler));
     }
 
-    pub fn resume_from(&mut self, instruction_number: u16, world: &mut dyn World) -> ExecutionEnd {
+    /// A handler may remove itself while it is running. It is dropped once it returns
+    /// and `None` is returned, as it isn't available yet.
+    pub fn remove_hook_handler(&mut self, hook: u32) -> Option<HookHandler> {
+        if let Some((running, removed)) = &mut self.running_hook {
+            if *running == hook {
+                *removed = true;
+            }
+        }
+        self.hook_handlers.remove(&hook)
+    }
+
+    /// Runs the handler for `hook`. Suspends if there is none.
+    pub(crate) fn handle_hook(&mut self, hook: u32, world: &mut dyn World) -> HookResult {
+        // The handler is taken out so that it can get the VM mutably.
+        let Some(mut handler) = self.hook_handlers.remove(&hook) else {
+            return HookResult::Suspend;
+        };
+        let outer = self.running_hook.replace((hook, false));
+        let result = handler(self, world);
+        let (_, removed) = std::mem::replace(&mut self.running_hook, outer).unwrap();
+        // Unless the handler has removed itself or registered a replacement
+        if !removed {
+            self.hook_handlers.entry(hook).or_insert(handler);
+        }
+        result
+    }
+
+    pub fn run<T: Tracer>(&mut self, world: &mut dyn World, tracer: &mut T) -> ExecutionEnd {
+        self.resume_from(0, world, tracer)
+    }
+
+    pub fn resume_from<T: Tracer>(
+        &mut self,
+        instruction_number: u16,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> ExecutionEnd {
         let mut instruction: *const Instruction =
             &self.state.current_frame.program.instructions()[instruction_number as usize];
 
         unsafe {
-            loop {
-                let args = &(*instruction).arguments;
-                let Ok(_) = self.state.use_gas(args.get_static_gas_cost()) else {
-                    instruction = match free_panic(self, world) {
-                        Ok(i) => i,
-                        Err(e) => return e,
-                    };
-                    continue;
-                };
-
-                #[cfg(trace)]
-                self.print_instruction(instruction);
-
-                if args.predicate.satisfied(&self.state.flags) {
-                    instruction = match ((*instruction).handler)(self, instruction, world) {
+            if tracer.is_noop() && self.interrupt_flag.is_none() {
+                loop {
+                    instruction = match self.execute_untraced(instruction, world) {
                         Ok(n) => n,
                         Err(e) => return e,
                     };
-                } else {
-                    instruction = instruction.add(1);
                 }
             }
+
+            loop {
+                instruction = match self.execute_instruction(instruction, world, tracer) {
+                    Ok(n) => n,
+                    Err(end) => return self.end_execution(end, tracer),
+                };
+            }
         }
     }
 
+    fn end_execution<T: Tracer>(&mut self, end: ExecutionEnd, tracer: &mut T) -> ExecutionEnd {
+        tracer.on_execution_end(self, &end);
+        end
+    }
+
     /// Returns how much of the extra gas limit is left and the stop reason,
     /// unless the extra gas limit was exceeded.
     ///
     /// Needed to support account validation gas limit.
     /// We cannot simply reduce the available gas, as contracts might behave differently
     /// depending on remaining gas.
-    pub fn resume_with_additional_gas_limit(
+    pub fn resume_with_additional_gas_limit<T: Tracer>(
         &mut self,
         instruction_number: u16,
         world: &mut dyn World,
+        tracer: &mut T,
         gas_limit: u32,
     ) -> Option<(u32, ExecutionEnd)> {
         let minimum_gas = self.state.total_unspent_gas().saturating_sub(gas_limit);
@@ -113,27 +218,11 @@ impl VirtualMachine {
 
         let end = unsafe {
             loop {
-                let args = &(*instruction).arguments;
-                let Ok(_) = self.state.use_gas(args.get_static_gas_cost()) else {
-                    instruction = match free_panic(self, world) {
-                        Ok(i) => i,
-                        Err(end) => break end,
-                    };
-                    continue;
+                instruction = match self.execute_instruction(instruction, world, tracer) {
+                    Ok(n) => n,
+                    Err(end) => break self.end_execution(end, tracer),
                 };
 
-                #[cfg(trace)]
-                self.print_instruction(instruction);
-
-                if args.predicate.satisfied(&self.state.flags) {
-                    instruction = match ((*instruction).handler)(self, instruction, world) {
-                        Ok(n) => n,
-                        Err(end) => break end,
-                    };
-                } else {
-                    instruction = instruction.add(1);
-                }
-
                 if self.state.total_unspent_gas() < minimum_gas {
                     return None;
                 }
@@ -146,6 +235,185 @@ impl VirtualMachine {
             .map(|left| (left, end))
     }
 
+    /// Executes at most `budget` instructions, then returns [ExecutionEnd::BudgetExhausted].
+    ///
+    /// Unlike gas, the budget bounds the work done by a single call, which is
+    /// useful for interleaving the execution of many VMs.
+    /// Instructions skipped because of their predicate count as executed.
+    pub fn resume_with_instruction_budget<T: Tracer>(
+        &mut self,
+        instruction_number: u16,
+        world: &mut dyn World,
+        tracer: &mut T,
+        budget: u64,
+    ) -> ExecutionEnd {
+        if budget == 0 {
+            let end = ExecutionEnd::BudgetExhausted {
+                pc_to_resume_from: instruction_number,
+            };
+            return self.end_execution(end, tracer);
+        }
+
+        let mut instruction: *const Instruction =
+            &self.state.current_frame.program.instructions()[instruction_number as usize];
+        let mut executed = 0;
+
+        unsafe {
+            loop {
+                instruction = match self.execute_instruction(instruction, world, tracer) {
+                    Ok(n) => n,
+                    Err(end) => return self.end_execution(end, tracer),
+                };
+
+                // Instructions outside of the program are part of the instruction that jumped to them.
+                if let Some(pc) = self.state.current_frame.pc_in_program(instruction) {
+                    executed += 1;
+                    if executed == budget {
+                        let end = ExecutionEnd::BudgetExhausted {
+                            pc_to_resume_from: pc,
+                        };
+                        return self.end_execution(end, tracer);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes the instruction at `instruction_number` in the current frame,
+    /// including paying its static gas cost and checking its predicate.
+    ///
+    /// Stepping until [Step::End] is equivalent to [Self::run].
+    pub fn step<T: Tracer>(
+        &mut self,
+        instruction_number: u16,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> Step {
+        let mut instruction: *const Instruction =
+            &self.state.current_frame.program.instructions()[instruction_number as usize];
+
+        unsafe {
+            loop {
+                instruction = match self.execute_instruction(instruction, world, tracer) {
+                    Ok(n) => n,
+                    Err(end) => return Step::End(self.end_execution(end, tracer)),
+                };
+
+                // A jump to an instruction that isn't part of the program is a panic
+                // that belongs to the instruction that caused it.
+                if let Some(pc) = self.state.current_frame.pc_in_program(instruction) {
+                    return Step::Continue {
+                        pc,
+                        frame: self.state.previous_frames.len(),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Pays for and executes a single instruction and returns the instruction to execute next.
+    ///
+    /// The instruction may be one of the instructions that are not part of any program,
+    /// like the one that panics. Those are not reported to the tracer
+    /// because they are just a continuation of the instruction that jumped to them.
+    #[inline(always)]
+    unsafe fn execute_instruction<T: Tracer>(
+        &mut self,
+        instruction: *const Instruction,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> InstructionResult {
+        if let Some(flag) = &self.interrupt_flag {
+            self.instructions_until_interrupt_check =
+                self.instructions_until_interrupt_check.saturating_sub(1);
+
+            if self.instructions_until_interrupt_check == 0 {
+                // Only instructions that are part of the program can be resumed from.
+                if let Some(pc) = self.state.current_frame.pc_in_program(instruction) {
+                    self.instructions_until_interrupt_check = INTERRUPT_CHECK_INTERVAL;
+                    if flag.load(Ordering::Relaxed) {
+                        return Err(ExecutionEnd::Interrupted {
+                            pc_to_resume_from: pc,
+                        });
+                    }
+                }
+            }
+        }
+
+        if tracer.is_noop() {
+            self.execute_untraced(instruction, world)
+        } else {
+            self.execute_traced(instruction, world, tracer)
+        }
+    }
+
+    /// Like [Self::execute_instruction] with the tracer that does nothing and without an interrupt flag.
+    /// This is all [Self::run] does per instruction in that case.
+    #[inline(always)]
+    unsafe fn execute_untraced(
+        &mut self,
+        instruction: *const Instruction,
+        world: &mut dyn World,
+    ) -> InstructionResult {
+        let args = &(*instruction).arguments;
+        if self.state.use_gas(args.get_static_gas_cost()).is_err() {
+            return self.panic_on_static_gas(instruction, world, &mut ());
+        }
+        if args.predicate.satisfied(&self.state.flags) {
+            ((*instruction).handler.untraced)(self, instruction, world, &mut ())
+        } else {
+            Ok(instruction.add(1))
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn execute_traced<T: Tracer>(
+        &mut self,
+        instruction: *const Instruction,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> InstructionResult {
+        let pc = self.state.current_frame.pc_in_program(instruction);
+        if let Some(pc) = pc {
+            if tracer.should_stop(self, pc) {
+                return Err(ExecutionEnd::StoppedByTracer {
+                    pc_to_resume_from: pc,
+                });
+            }
+            tracer.before_instruction(self, pc);
+        }
+
+        let args = &(*instruction).arguments;
+        let result = if self.state.use_gas(args.get_static_gas_cost()).is_err() {
+            self.panic_on_static_gas(instruction, world, tracer)
+        } else if args.predicate.satisfied(&self.state.flags) {
+            ((*instruction).handler.traced)(self, instruction, world, tracer)
+        } else {
+            Ok(instruction.add(1))
+        };
+
+        if let Some(pc) = pc {
+            tracer.after_instruction(self, pc);
+        }
+        self.state.advance_cycle();
+        result
+    }
+
+    #[cold]
+    unsafe fn panic_on_static_gas<T: Tracer + ?Sized>(
+        &mut self,
+        instruction: *const Instruction,
+        world: &mut dyn World,
+        tracer: &mut T,
+    ) -> InstructionResult {
+        let reason = if (*instruction).arguments.get_static_gas_cost() == INVALID_INSTRUCTION_COST {
+            PanicReason::InvalidInstruction
+        } else {
+            PanicReason::OutOfGas
+        };
+        free_panic(self, instruction, reason, world, tracer)
+    }
+
     /// Returns a compact representation of the VM's current state,
     /// including pending side effects like storage changes and emitted events.
     /// [VirtualMachine::rollback] can be used to return the VM to this state.
@@ -160,19 +428,51 @@ impl VirtualMachine {
     }
 
     /// Returns the VM to the state it was in when the snapshot was created.
+    ///
+    /// [State::timestamp] and [State::monotonic_cycle_counter] are not rolled back,
+    /// so that queries made afterwards are ordered after the ones that were rolled back.
     /// # Panics
     /// Rolling back snapshots in anything but LIFO order may panic.
     pub fn rollback(&mut self, snapshot: VmSnapshot) {
+        let (timestamp, monotonic_cycle_counter) =
+            (self.state.timestamp, self.state.monotonic_cycle_counter);
+        self.restore(snapshot);
+        self.state.timestamp = timestamp;
+        self.state.monotonic_cycle_counter = monotonic_cycle_counter;
+    }
+
+    /// Like [VirtualMachine::rollback] but restores the timestamp and cycle counter too.
+    pub(crate) fn restore(&mut self, snapshot: VmSnapshot) {
         self.world_diff.external_rollback(snapshot.world_snapshot);
         self.state = snapshot.state_snapshot;
     }
 
+    /// Like [VirtualMachine::snapshot] but can be taken in any frame and doesn't copy the heaps,
+    /// which have to be passed to [VirtualMachine::restore_with_heaps].
+    /// Only valid as long as [VirtualMachine::can_roll_back_to] holds.
+    pub(crate) fn checkpoint(&self) -> VmSnapshot {
+        VmSnapshot {
+            world_snapshot: self.world_diff.checkpoint(),
+            state_snapshot: self.state.clone_without_heaps(),
+        }
+    }
+
+    pub(crate) fn restore_with_heaps(&mut self, snapshot: VmSnapshot, heaps: Heaps) {
+        self.restore(snapshot);
+        self.state.heaps = heaps;
+    }
+
+    pub(crate) fn can_roll_back_to(&self, snapshot: &VmSnapshot) -> bool {
+        self.world_diff.can_roll_back_to(&snapshot.world_snapshot)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn push_frame<const CALLING_MODE: u8>(
         &mut self,
         instruction_pointer: *const Instruction,
         code_address: H160,
         program: Program,
+        code_hash: U256,
         gas: u32,
         stipend: u32,
         exception_handler: u16,
@@ -218,6 +518,7 @@ impl VirtualMachine {
             is_static || self.state.current_frame.is_static,
             world_before_this_frame,
         );
+        new_frame.code_hash = Some(code_hash);
         self.state.context_u128 = 0;
 
         let old_pc = self.state.current_frame.pc_to_u16(instruction_pointer);
@@ -267,31 +568,25 @@ impl VirtualMachine {
             })
     }
 
-    #[cfg(trace)]
-    fn print_instruction(&self, instruction: *const Instruction) {
-        print!("{:?}: ", unsafe {
-            instruction.offset_from(&self.state.current_frame.program.instructions()[0])
-        });
-        self.state.registers[1..]
-            .iter()
-            .zip(1..)
-            .for_each(|(&(mut x), i)| {
-                if self.state.register_pointer_flags & (1 << i) != 0 {
-                    x.0[0] &= 0x00000000_ffffffffu64;
-                    x.0[1] &= 0xffffffff_00000000u64;
-                }
-                print!("{x:?} ")
-            });
-        print!("{}", self.state.current_frame.gas);
-        println!();
-    }
-
     pub(crate) fn start_new_tx(&mut self) {
         self.state.transaction_number = self.state.transaction_number.wrapping_add(1);
         self.world_diff.clear_transient_storage()
     }
 }
 
+/// The outcome of [VirtualMachine::step].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Execution should continue at instruction `pc` of the current frame.
+    /// `frame` is the number of far calls the current frame is nested in, so zero for the initial frame.
+    Continue {
+        pc: u16,
+        frame: usize,
+    },
+    End(ExecutionEnd),
+}
+
+#[derive(Clone)]
 pub struct VmSnapshot {
     world_snapshot: ExternalSnapshot,
     state_snapshot: State,
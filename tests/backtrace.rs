@@ -0,0 +1,143 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Immediate2, Register, Register1, Register2,
+        RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode},
+    testworld::TestWorld,
+    Backtrace, BacktraceFrame, BacktraceNearCall, ExecutionEnd, Instruction, Predicate, Program,
+    VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+const MAIN_ADDRESS: u64 = 0xfeddeadbeef;
+const CALLEE_ADDRESS: u64 = 0xca11ee;
+
+/// The callee reverts inside a near call and propagates that revert.
+/// `on_failed_call` is what the main program does after the far call to the callee fails.
+fn run(on_failed_call: Instruction) -> (ExecutionEnd, Option<Backtrace>) {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let callee_address = Address::from_low_u64_be(CALLEE_ADDRESS);
+    let mut abi = U256::zero();
+    abi.0[3] = 5000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+
+    let main_program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(4),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            on_failed_call,
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+
+    let callee = Program::new(
+        vec![
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(2),
+                Immediate2(3),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let main_address = Address::from_low_u64_be(MAIN_ADDRESS);
+    let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+    let program = initial_decommit(&mut world, main_address);
+
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        10_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            record_failure_backtraces: true,
+        },
+    );
+
+    let end = vm.run(&mut world, &mut ());
+    (end, vm.failure_backtrace().cloned())
+}
+
+#[test]
+fn backtrace_points_to_innermost_failure() {
+    let (end, backtrace) = run(Instruction::from_revert(
+        Register1(Register::new(0)),
+        None,
+        Arguments::new(Predicate::Always, 5),
+    ));
+    assert_eq!(end, ExecutionEnd::Reverted(vec![]));
+
+    let main_address = Address::from_low_u64_be(MAIN_ADDRESS);
+    let callee_address = Address::from_low_u64_be(CALLEE_ADDRESS);
+    assert_eq!(
+        backtrace,
+        Some(Backtrace(vec![
+            BacktraceFrame {
+                address: main_address,
+                code_address: main_address,
+                caller: Address::zero(),
+                pc: 2,
+                near_calls: vec![],
+            },
+            BacktraceFrame {
+                address: callee_address,
+                code_address: callee_address,
+                caller: main_address,
+                pc: 2,
+                near_calls: vec![BacktraceNearCall {
+                    call_instruction: 0,
+                    exception_handler: 3,
+                }],
+            },
+        ]))
+    );
+}
+
+#[test]
+fn handled_failure_has_no_backtrace() {
+    let (end, backtrace) = run(Instruction::from_ret(
+        Register1(Register::new(0)),
+        None,
+        Arguments::new(Predicate::Always, 5),
+    ));
+    assert_eq!(end, ExecutionEnd::ProgramFinished(vec![]));
+    assert_eq!(backtrace, None);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::create_looping_vm;
+use u256::U256;
+use vm2::ExecutionEnd;
+
+#[test]
+fn infinite_loop_is_interrupted_by_budget() {
+    let (mut vm, mut world) = create_looping_vm(1_000_000);
+
+    assert_eq!(
+        vm.resume_with_instruction_budget(0, &mut world, &mut (), 5),
+        ExecutionEnd::BudgetExhausted {
+            pc_to_resume_from: 1
+        }
+    );
+    assert_eq!(vm.state.registers[2], U256::from(3));
+
+    assert_eq!(
+        vm.resume_with_instruction_budget(1, &mut world, &mut (), 4),
+        ExecutionEnd::BudgetExhausted {
+            pc_to_resume_from: 1
+        }
+    );
+    assert_eq!(vm.state.registers[2], U256::from(5));
+
+    assert_eq!(
+        vm.resume_with_instruction_budget(1, &mut world, &mut (), 0),
+        ExecutionEnd::BudgetExhausted {
+            pc_to_resume_from: 1
+        }
+    );
+    assert_eq!(vm.state.registers[2], U256::from(5));
+}
+
+#[test]
+fn budget_does_not_change_outcome() {
+    let (mut vm, mut world) = create_looping_vm(1000);
+    let expected = vm.run(&mut world, &mut ());
+
+    let (mut budgeted_vm, mut world) = create_looping_vm(1000);
+    let mut pc = 0;
+    let end = loop {
+        match budgeted_vm.resume_with_instruction_budget(pc, &mut world, &mut (), 7) {
+            ExecutionEnd::BudgetExhausted { pc_to_resume_from } => pc = pc_to_resume_from,
+            end => break end,
+        }
+    };
+
+    assert_eq!(end, expected);
+    assert_eq!(budgeted_vm.state.registers, vm.state.registers);
+}
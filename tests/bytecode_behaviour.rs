@@ -1,24 +1,11 @@
-use u256::U256;
 use vm2::{
-    decode::decode_program, initial_decommit, testworld::TestWorld, ExecutionEnd, Program,
+    decode::program_from_bytecode, initial_decommit, testworld::TestWorld, ExecutionEnd, Program,
     VirtualMachine,
 };
 use zkevm_opcode_defs::ethereum_types::Address;
 
 fn program_from_file(filename: &str) -> Program {
-    let blob = std::fs::read(filename).unwrap();
-    Program::new(
-        decode_program(
-            &blob
-                .chunks_exact(8)
-                .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
-                .collect::<Vec<_>>(),
-            false,
-        ),
-        blob.chunks_exact(32)
-            .map(|chunk| U256::from_big_endian(chunk.try_into().unwrap()))
-            .collect::<Vec<_>>(),
-    )
+    program_from_bytecode(&std::fs::read(filename).unwrap(), false)
 }
 
 #[test]
@@ -41,8 +28,12 @@ fn call_to_invalid_address() {
             default_aa_code_hash: [0; 32],
             evm_interpreter_code_hash: [0; 32],
             hook_address: 0,
+            ..Default::default()
         },
     );
-    assert!(matches!(vm.run(&mut world), ExecutionEnd::Panicked));
+    assert!(matches!(
+        vm.run(&mut world, &mut ()),
+        ExecutionEnd::Panicked(_)
+    ));
     assert_eq!(vm.state.current_frame.gas, 0);
 }
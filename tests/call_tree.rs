@@ -0,0 +1,235 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Register, Register1, Register2, RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode},
+    testworld::TestWorld,
+    tracers::{Call, CallOutcome, CallTracer},
+    ExecutionEnd, Instruction, PanicReason, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn records_successful_and_failed_calls() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let callee_address = Address::from_low_u64_be(0xca11ee);
+
+    // Passes four bytes of the heap as calldata
+    let mut abi = U256::zero();
+    abi.0[1] = 4 << 32;
+    abi.0[3] = 20_000;
+
+    // Forwarding a fat pointer without having one is invalid
+    let mut invalid_abi = U256::zero();
+    invalid_abi.0[3] = 1000 | (1 << 32);
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let far_call = |exception_handler| {
+        Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+            Register1(r1),
+            Register2(r2),
+            Immediate1(exception_handler),
+            false,
+            Arguments::new(Predicate::Always, 200),
+        )
+    };
+
+    let main_program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            far_call(3),
+            load_from_code_page(2, r1),
+            load_from_code_page(1, r2),
+            far_call(6),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![
+            abi,
+            U256::from_big_endian(callee_address.as_bytes()),
+            invalid_abi,
+        ],
+    );
+    let callee = Program::new(
+        vec![Instruction::from_ret(
+            Register1(r0),
+            None,
+            Arguments::new(Predicate::Always, 5),
+        )],
+        vec![],
+    );
+
+    let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+    let program = initial_decommit(&mut world, main_address);
+
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![1, 2, 3],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut tracer = CallTracer::new(&vm);
+    assert_eq!(
+        vm.run(&mut world, &mut tracer),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    let child = |calldata, gas, gas_used, outcome| Call {
+        mode: CallingMode::Normal,
+        address: callee_address,
+        code_address: callee_address,
+        caller: main_address,
+        calldata,
+        gas,
+        gas_used,
+        stipend_applied: false,
+        context_u128: 0,
+        is_static: false,
+        outcome: Some(outcome),
+        calls: vec![],
+    };
+    assert_eq!(
+        tracer.root(),
+        Some(&Call {
+            mode: CallingMode::Normal,
+            address: main_address,
+            code_address: main_address,
+            caller: Address::zero(),
+            calldata: vec![1, 2, 3],
+            gas: 100_000,
+            gas_used: 100_000 - vm.state.current_frame.gas,
+            stipend_applied: false,
+            context_u128: 0,
+            is_static: false,
+            outcome: Some(CallOutcome::Returned(vec![])),
+            calls: vec![
+                child(vec![0; 4], 20_000, 5, CallOutcome::Returned(vec![])),
+                child(
+                    vec![],
+                    1000,
+                    1000,
+                    CallOutcome::Panicked(PanicReason::InvalidCalldata)
+                ),
+            ],
+        })
+    );
+}
+
+#[test]
+fn geth_json() {
+    let address = |x| Address::from_low_u64_be(x);
+    let mut revert_data = vec![0x08, 0xc3, 0x79, 0xa0];
+    revert_data.extend([0; 31]);
+    revert_data.push(0x20);
+    revert_data.extend([0; 31]);
+    revert_data.push(3);
+    revert_data.extend(b"no\"");
+    revert_data.resize(4 + 96, 0);
+
+    let call = Call {
+        mode: CallingMode::Normal,
+        address: address(1),
+        code_address: address(1),
+        caller: address(0xaa),
+        calldata: vec![0xab, 0xcd],
+        gas: 1000,
+        gas_used: 300,
+        stipend_applied: false,
+        context_u128: 0,
+        is_static: false,
+        outcome: Some(CallOutcome::Returned(vec![1])),
+        calls: vec![
+            Call {
+                mode: CallingMode::Delegate,
+                address: address(1),
+                code_address: address(2),
+                caller: address(0xaa),
+                calldata: vec![],
+                gas: 100,
+                gas_used: 100,
+                stipend_applied: false,
+                context_u128: 0,
+                is_static: false,
+                outcome: Some(CallOutcome::Panicked(PanicReason::OutOfGas)),
+                calls: vec![],
+            },
+            Call {
+                mode: CallingMode::Normal,
+                address: address(3),
+                code_address: address(3),
+                caller: address(1),
+                calldata: vec![],
+                gas: 100,
+                gas_used: 10,
+                stipend_applied: false,
+                context_u128: 0,
+                is_static: true,
+                outcome: Some(CallOutcome::Reverted(revert_data.clone())),
+                // Inherits static mode from its caller
+                calls: vec![Call {
+                    mode: CallingMode::Normal,
+                    address: address(4),
+                    code_address: address(4),
+                    caller: address(3),
+                    calldata: vec![],
+                    gas: 50,
+                    gas_used: 5,
+                    stipend_applied: false,
+                    context_u128: 0,
+                    is_static: true,
+                    outcome: Some(CallOutcome::Returned(vec![])),
+                    calls: vec![],
+                }],
+            },
+        ],
+    };
+
+    let hex_address = |x| format!("0x{:040x}", x);
+    let revert_hex: String = revert_data.iter().map(|b| format!("{b:02x}")).collect();
+    assert_eq!(
+        call.to_geth_json(&Default::default()),
+        format!(
+            concat!(
+                r#"{{"type":"CALL","from":"{aa}","to":"{one}","input":"0xabcd","gas":"0x3e8","gasUsed":"0x12c","output":"0x01","calls":["#,
+                r#"{{"type":"DELEGATECALL","from":"{one}","to":"{two}","input":"0x","gas":"0x64","gasUsed":"0x64","error":"out of gas"}},"#,
+                r#"{{"type":"STATICCALL","from":"{one}","to":"{three}","input":"0x","gas":"0x64","gasUsed":"0xa","output":"0x{revert}","error":"execution reverted","revertReason":"no\"","calls":["#,
+                r#"{{"type":"CALL","from":"{three}","to":"{four}","input":"0x","gas":"0x32","gasUsed":"0x5","output":"0x"}}"#,
+                "]}}]}}"
+            ),
+            aa = hex_address(0xaa),
+            one = hex_address(1),
+            two = hex_address(2),
+            three = hex_address(3),
+            four = hex_address(4),
+            revert = revert_hex,
+        )
+    );
+}
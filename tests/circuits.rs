@@ -0,0 +1,142 @@
+mod common;
+
+use common::ret;
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Register, Register1, Register2, RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode},
+    testworld::TestWorld,
+    tracers::{CircuitEstimator, CircuitUsage},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::{ethereum_types::Address, ADDRESS_EVENT_WRITER};
+
+#[test]
+fn counts_per_transaction() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+    let r3 = Register::new(3);
+    let r4 = Register::new(4);
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let callee_address = Address::from_low_u64_be(ADDRESS_EVENT_WRITER as u64);
+
+    let mut abi = U256::zero();
+    abi.0[3] = 20_000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let far_call = || {
+        Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+            Register1(r1),
+            Register2(r2),
+            Immediate1(11),
+            false,
+            Arguments::new(Predicate::Always, 200),
+        )
+    };
+
+    let main = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            far_call(),
+            // The second call doesn't decommit the code again
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            far_call(),
+            Instruction::from_sload(
+                Register1(r3),
+                Register1(r4),
+                Arguments::new(Predicate::Always, 2008),
+            ),
+            Instruction::from_sstore(
+                Register1(r3),
+                Register2(r4),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_increment_tx_number(Arguments::new(Predicate::Always, 5)),
+            Instruction::from_l2_to_l1_message(
+                Register1(r3),
+                Register2(r4),
+                false,
+                Arguments::new(Predicate::Always, 100),
+            ),
+            ret(),
+            ret(),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+    let callee = Program::new(
+        vec![
+            Instruction::from_event(
+                Register1(r2),
+                Register2(r2),
+                true,
+                Arguments::new(Predicate::Always, 100),
+            ),
+            ret(),
+        ],
+        vec![U256::zero(); 3],
+    );
+
+    let mut world = TestWorld::new(&[(main_address, main), (callee_address, callee)]);
+    let program = initial_decommit(&mut world, main_address);
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut estimator = CircuitEstimator::new();
+    assert_eq!(
+        vm.run(&mut world, &mut estimator),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    let first = CircuitUsage {
+        cycles: 9 + 2 * 2,
+        decommits: 1,
+        decommitted_code_words: 3,
+        storage_reads: 3,
+        storage_writes: 1,
+        events: 2,
+        ..Default::default()
+    };
+    let second = CircuitUsage {
+        cycles: 2,
+        l2_to_l1_logs: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        estimator.transactions().iter().collect::<Vec<_>>(),
+        vec![(&0, &first), (&1, &second)]
+    );
+    assert_eq!(estimator.total().cycles, 15);
+    assert_eq!(estimator.total().storage_reads, 3);
+}
@@ -0,0 +1,61 @@
+//! Fixtures shared by the integration tests.
+// Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::Add,
+    testworld::TestWorld,
+    Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+/// A VM running a program that increments r2 forever.
+pub fn create_looping_vm(gas: u32) -> (VirtualMachine, TestWorld) {
+    let r2 = Register::new(2);
+    let program = Program::new(
+        vec![
+            Instruction::from_binop::<Add>(
+                Immediate1(1).into(),
+                Register2(r2),
+                Register1(r2).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                false,
+            ),
+            Instruction::from_jump(Immediate1(0).into(), Arguments::new(Predicate::Always, 6)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        gas,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    (vm, world)
+}
+
+/// Returns successfully without return data.
+pub fn ret() -> Instruction {
+    Instruction::from_ret(
+        Register1(Register::new(0)),
+        None,
+        Arguments::new(Predicate::Always, 5),
+    )
+}
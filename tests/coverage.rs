@@ -0,0 +1,154 @@
+use u256::U256;
+use vm2::{
+    address_into_u256,
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Register, Register1, Register2, RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode, Sub},
+    testworld::TestWorld,
+    tracers::{BranchCoverage, CoverageTracer, SourceLine},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn counts_hits_and_branches() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let callee_address = Address::from_low_u64_be(0xca11ee);
+
+    let mut abi = U256::zero();
+    abi.0[3] = 20_000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let predicated_add = |predicate| {
+        Instruction::from_binop::<Add>(
+            Register1(r2).into(),
+            Register2(r2),
+            Register1(r2).into(),
+            (),
+            Arguments::new(predicate, 6),
+            false,
+            false,
+        )
+    };
+    let ret = || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+    let main_program = Program::new(
+        vec![
+            // Sets the equal flag
+            Instruction::from_binop::<Sub>(
+                Register1(r0).into(),
+                Register2(r0),
+                Register1(r2).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                true,
+            ),
+            predicated_add(Predicate::IfEQ),
+            predicated_add(Predicate::IfGT),
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(7),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            ret(),
+            ret(),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+    let callee = Program::new(vec![ret(), ret()], vec![]);
+
+    let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+    let main_hash = world.address_to_hash[&address_into_u256(main_address)];
+    let callee_hash = world.address_to_hash[&address_into_u256(callee_address)];
+    let program = initial_decommit(&mut world, main_address);
+
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut coverage = CoverageTracer::new(main_hash);
+    assert_eq!(
+        vm.run(&mut world, &mut coverage),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    let main = &coverage.programs()[&main_hash];
+    assert_eq!(main.hits, vec![1, 1, 1, 1, 1, 1, 1, 0]);
+    assert_eq!(
+        main.branches
+            .iter()
+            .map(|(pc, b)| (*pc, *b))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                1,
+                BranchCoverage {
+                    taken: 1,
+                    not_taken: 0
+                }
+            ),
+            (
+                2,
+                BranchCoverage {
+                    taken: 0,
+                    not_taken: 1
+                }
+            ),
+        ]
+    );
+    assert_eq!(coverage.programs()[&callee_hash].hits, vec![1, 0]);
+
+    let lcov = coverage.to_lcov();
+    assert_eq!(lcov.matches("end_of_record").count(), 2);
+    assert!(lcov.contains("BRDA:2,0,0,1\nBRDA:2,0,1,0\nBRDA:3,0,0,0\nBRDA:3,0,1,1\nBRF:4\nBRH:2\n"));
+    assert!(lcov.contains("DA:7,1\nDA:8,0\nLF:8\nLH:7\n"));
+
+    // The whole main program is on two lines and the callee isn't mapped.
+    let lcov = coverage.to_lcov_with_source_map(|hash, pc| {
+        (hash == main_hash).then(|| SourceLine {
+            file: "main.sol".to_string(),
+            line: if pc < 7 { 10 } else { 11 },
+        })
+    });
+    assert_eq!(
+        lcov,
+        "SF:main.sol\n\
+         BRDA:10,0,0,1\nBRDA:10,0,1,0\nBRDA:10,1,0,0\nBRDA:10,1,1,1\nBRF:4\nBRH:2\n\
+         DA:10,1\nDA:11,0\nLF:2\nLH:1\nend_of_record\n"
+    );
+}
@@ -0,0 +1,170 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        AdvanceStackPointer, Arguments, Immediate1, Register, Register1, Register2,
+        RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::Heap,
+    testworld::TestWorld,
+    tracers::{Debugger, Hit},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine, FIRST_HEAP,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn stops_at_breakpoints_and_watchpoints() {
+    let r0 = Register::new(0);
+    let sstore = || {
+        Instruction::from_sstore(
+            Register1(r0),
+            Register2(r0),
+            Arguments::new(Predicate::Always, 5511),
+        )
+    };
+    let no_sp_change = || {
+        AdvanceStackPointer(RegisterAndImmediate {
+            immediate: 0,
+            register: r0,
+        })
+    };
+    let program = Program::new(
+        vec![
+            Instruction::from_nop(
+                no_sp_change(),
+                no_sp_change(),
+                Arguments::new(Predicate::Always, 6),
+            ),
+            sstore(),
+            Instruction::from_store::<Heap>(
+                Immediate1(64).into(),
+                Register2(r0),
+                None,
+                Arguments::new(Predicate::Always, 6),
+                false,
+            ),
+            sstore(),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut debugger = Debugger::default();
+    debugger.add_breakpoint(address, 1);
+    // Right after the heap write
+    debugger.add_breakpoint(address, 3);
+    debugger.watch_storage(address, U256::zero());
+    // Writes by precompiles are reported the same way as the heap write below.
+    debugger.watch_heap(FIRST_HEAP, 70..71);
+
+    let mut expect_stop = |pc: u16, hit: Hit, vm: &mut VirtualMachine, debugger: &mut Debugger| {
+        let end = vm.resume_from(pc, &mut world, debugger);
+        let ExecutionEnd::StoppedByTracer { pc_to_resume_from } = end else {
+            panic!("expected to stop but got {end:?}");
+        };
+        assert_eq!(debugger.last_hit(), Some(&hit));
+        pc_to_resume_from
+    };
+
+    let pc = expect_stop(
+        0,
+        Hit::Breakpoint {
+            code_address: address,
+            pc: 1,
+        },
+        &mut vm,
+        &mut debugger,
+    );
+    assert_eq!(pc, 1);
+
+    let pc = expect_stop(
+        pc,
+        Hit::StorageWrite {
+            contract: address,
+            key: U256::zero(),
+            value: U256::zero(),
+        },
+        &mut vm,
+        &mut debugger,
+    );
+    assert_eq!(pc, 2);
+
+    let pc = expect_stop(
+        pc,
+        Hit::HeapWrite {
+            heap: FIRST_HEAP,
+            address: 64,
+            value: U256::zero(),
+        },
+        &mut vm,
+        &mut debugger,
+    );
+    assert_eq!(pc, 3);
+
+    // Stopping for the watchpoint doesn't hide the breakpoint.
+    let pc = expect_stop(
+        pc,
+        Hit::Breakpoint {
+            code_address: address,
+            pc: 3,
+        },
+        &mut vm,
+        &mut debugger,
+    );
+    assert_eq!(pc, 3);
+
+    debugger.unwatch_storage(address, U256::zero());
+    assert_eq!(
+        vm.resume_from(pc, &mut world, &mut debugger),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    // Abandoning a stopped run doesn't affect the next one.
+    let new_vm = |world: &mut TestWorld| {
+        VirtualMachine::new(
+            address,
+            initial_decommit(world, address),
+            Address::zero(),
+            vec![],
+            100_000,
+            vm2::Settings {
+                default_aa_code_hash: [0; 32],
+                evm_interpreter_code_hash: [0; 32],
+                hook_address: 0,
+                ..Default::default()
+            },
+        )
+    };
+    debugger.remove_breakpoint(address, 1);
+    assert_eq!(
+        new_vm(&mut world).run(&mut world, &mut debugger),
+        ExecutionEnd::StoppedByTracer {
+            pc_to_resume_from: 3
+        }
+    );
+    debugger.add_breakpoint(address, 0);
+    assert_eq!(
+        new_vm(&mut world).run(&mut world, &mut debugger),
+        ExecutionEnd::StoppedByTracer {
+            pc_to_resume_from: 0
+        }
+    );
+}
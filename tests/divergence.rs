@@ -0,0 +1,153 @@
+mod common;
+
+use common::{create_looping_vm, ret};
+use std::sync::{atomic::AtomicBool, Arc};
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Register, Register1, Register2},
+    compare_traces, find_divergence, initial_decommit,
+    instruction_handlers::Add,
+    testworld::TestWorld,
+    tracers::StepRecorder,
+    Difference, ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+fn set(register: u8, value: u16) -> Instruction {
+    Instruction::from_binop::<Add>(
+        Immediate1(value).into(),
+        Register2(Register::new(0)),
+        Register1(Register::new(register)).into(),
+        (),
+        Arguments::new(Predicate::Always, 6),
+        false,
+        false,
+    )
+}
+
+fn vm_and_world(instructions: Vec<Instruction>) -> (VirtualMachine, TestWorld) {
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, Program::new(instructions, vec![]))]);
+    let program = initial_decommit(&mut world, address);
+
+    let vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+    (vm, world)
+}
+
+#[test]
+fn finds_first_difference() {
+    let program = || vec![set(2, 1), set(3, 2), set(4, 3), ret()];
+    let changed = || vec![set(2, 1), set(3, 2), set(4, 4), ret()];
+
+    let (mut left_vm, mut left_world) = vm_and_world(program());
+    let (mut right_vm, mut right_world) = vm_and_world(program());
+    assert_eq!(
+        find_divergence(
+            &mut left_vm,
+            &mut left_world,
+            &mut right_vm,
+            &mut right_world,
+            2
+        ),
+        None
+    );
+
+    let (mut left_vm, mut left_world) = vm_and_world(program());
+    let (mut right_vm, mut right_world) = vm_and_world(changed());
+    let divergence = find_divergence(
+        &mut left_vm,
+        &mut left_world,
+        &mut right_vm,
+        &mut right_world,
+        1,
+    )
+    .unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(divergence.difference, Difference::Register(4));
+    assert_eq!(divergence.context.len(), 1);
+    assert_eq!(divergence.context[0].pc, 1);
+    assert_eq!(divergence.left.as_ref().unwrap().pc, 2);
+    assert!(divergence
+        .to_string()
+        .starts_with("diverged after 2 steps: r4 differs\n"));
+
+    // One program ends earlier
+    let (mut left_vm, mut left_world) = vm_and_world(program());
+    let (mut right_vm, mut right_world) = vm_and_world(vec![set(2, 1), ret()]);
+    let divergence = find_divergence(
+        &mut left_vm,
+        &mut left_world,
+        &mut right_vm,
+        &mut right_world,
+        0,
+    )
+    .unwrap();
+    assert_eq!(divergence.step, 1);
+    assert_eq!(divergence.difference, Difference::Location);
+
+    let record = |instructions| {
+        let (mut vm, mut world) = vm_and_world(instructions);
+        let mut recorder = StepRecorder::new();
+        assert_eq!(
+            vm.run(&mut world, &mut recorder),
+            ExecutionEnd::ProgramFinished(vec![])
+        );
+        recorder.into_steps()
+    };
+    let trace = record(program());
+    assert_eq!(trace.len(), 4);
+    assert_eq!(compare_traces(&trace, &record(program()), 3), None);
+
+    let divergence = compare_traces(&trace, &record(changed()), 3).unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(divergence.difference, Difference::Register(4));
+    assert_eq!(divergence.context, trace[..2]);
+
+    let divergence = compare_traces(&trace, &trace[..3], 3).unwrap();
+    assert_eq!(divergence.step, 3);
+    assert_eq!(
+        divergence.difference,
+        Difference::End {
+            left: None,
+            right: None
+        }
+    );
+    assert_eq!(divergence.right, None);
+}
+
+#[test]
+fn execution_ending_before_an_instruction_is_a_difference() {
+    let (mut left_vm, mut left_world) = create_looping_vm(1_000_000);
+    let (mut right_vm, mut right_world) = create_looping_vm(1_000_000);
+    left_vm.set_interrupt_flag(Arc::new(AtomicBool::new(true)));
+
+    // The interrupt flag is checked before the instruction, so the last step executes nothing.
+    let divergence = find_divergence(
+        &mut left_vm,
+        &mut left_world,
+        &mut right_vm,
+        &mut right_world,
+        0,
+    )
+    .unwrap();
+    assert!(matches!(
+        divergence.difference,
+        Difference::End {
+            left: Some(ExecutionEnd::Interrupted { .. }),
+            right: None
+        }
+    ));
+    assert_eq!(divergence.left, None);
+    assert!(divergence.right.is_some());
+}
@@ -0,0 +1,108 @@
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::Add,
+    testworld::TestWorld,
+    tracers::{Function, FunctionProfiler, FunctionStats},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn measures_inclusive_and_exclusive_gas() {
+    let r0 = Register::new(0);
+    let r2 = Register::new(2);
+
+    let near_call = |destination| {
+        Instruction::from_near_call(
+            Register1(r0),
+            Immediate1(destination),
+            Immediate2(0xFFFF),
+            Arguments::new(Predicate::Always, 25),
+        )
+    };
+    let ret = || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+    let program = Program::new(
+        vec![
+            near_call(3),
+            near_call(3),
+            ret(),
+            // Function calling another function
+            near_call(5),
+            ret(),
+            // Leaf function
+            Instruction::from_binop::<Add>(
+                Immediate1(1).into(),
+                Register2(r2),
+                Register1(r2).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                false,
+            ),
+            ret(),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        1000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut profiler = FunctionProfiler::new();
+    assert_eq!(
+        vm.run(&mut world, &mut profiler),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    let function = |label| Function {
+        code_address: address,
+        label,
+    };
+    let outer = profiler.functions()[&function(3)];
+    let leaf = profiler.functions()[&function(5)];
+
+    // All gas is passed because the gas register is zero
+    assert_eq!(outer.gas_passed, (1000 - 25) + (1000 - 25 - 41 - 25));
+    assert_eq!(outer.gas_passed - outer.gas_returned, 82);
+    assert_eq!(
+        outer,
+        FunctionStats {
+            calls: 2,
+            failed_calls: 0,
+            gas_passed: outer.gas_passed,
+            gas_returned: outer.gas_returned,
+            // near call, leaf function and return
+            inclusive_gas: 2 * (25 + 11 + 5),
+            exclusive_gas: 2 * (25 + 5),
+        }
+    );
+    assert_eq!(
+        (leaf.calls, leaf.inclusive_gas, leaf.exclusive_gas),
+        (2, 22, 22)
+    );
+
+    assert_eq!(
+        profiler
+            .by_exclusive_gas()
+            .iter()
+            .map(|(function, _)| function.label)
+            .collect::<Vec<_>>(),
+        vec![3, 5]
+    );
+}
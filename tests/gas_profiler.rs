@@ -0,0 +1,116 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Register, Register1, Register2, RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode},
+    testworld::TestWorld,
+    tracers::{GasProfiler, StackFrame},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn attributes_gas_to_far_call_stack() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let callee_address = Address::from_low_u64_be(0xca11ee);
+
+    let mut abi = U256::zero();
+    abi.0[3] = 20_000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let ret = || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+    let main_program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(4),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            ret(),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+    let callee = Program::new(vec![load_from_code_page(0, r2), ret()], vec![U256::one()]);
+
+    let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+    let program = initial_decommit(&mut world, main_address);
+
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut profiler = GasProfiler::new();
+    assert_eq!(
+        vm.run(&mut world, &mut profiler),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    let main = |pc| StackFrame {
+        code_address: main_address,
+        pc,
+    };
+    let in_callee = |pc| {
+        vec![
+            main(2),
+            StackFrame {
+                code_address: callee_address,
+                pc,
+            },
+        ]
+    };
+
+    let stacks = profiler.stacks();
+    let usage = |stack: Vec<StackFrame>| stacks.iter().find(|(s, _)| *s == stack).unwrap().1;
+    assert_eq!(stacks.len(), 6);
+    assert_eq!(usage(vec![main(0)]).spent, 6);
+    assert_eq!(usage(vec![main(3)]).spent, 5);
+    assert_eq!(usage(in_callee(0)).spent, 6);
+    assert_eq!(usage(in_callee(1)).spent, 5);
+
+    let far_call = usage(vec![main(2)]);
+    assert!(far_call.spent >= 200);
+    assert_eq!(far_call.passed, 20_000);
+    assert_eq!(far_call.passed - far_call.returned, 11);
+
+    let total = stacks.iter().map(|(_, usage)| usage.spent).sum::<i64>();
+    assert_eq!(total, (100_000 - vm.state.current_frame.gas) as i64);
+
+    let folded = profiler.folded_stacks();
+    assert_eq!(folded.lines().count(), 6);
+    assert!(folded.contains(&format!("{main_address:#x}:2;{callee_address:#x}:0 6\n")));
+}
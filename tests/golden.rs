@@ -0,0 +1,296 @@
+//! Compares execution traces of fixed scenarios to the files in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDENS=1` to overwrite the files with the current traces after an intended change.
+
+mod common;
+
+use common::ret;
+use std::fmt::Write;
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Immediate2, Register, Register1, Register2,
+        RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode, ReturnType},
+    testworld::TestWorld,
+    Event, Instruction, PanicReason, Predicate, Program, Settings, StorageWrite, Tracer,
+    VirtualMachine,
+};
+use zkevm_opcode_defs::{ethereum_types::Address, ADDRESS_EVENT_WRITER};
+
+struct Scenario {
+    contracts: Vec<(Address, Program)>,
+    storage: Vec<((Address, U256), U256)>,
+    calldata: Vec<u8>,
+    gas: u32,
+    settings: Settings,
+}
+
+impl Scenario {
+    fn new(contracts: Vec<(Address, Program)>) -> Self {
+        Self {
+            contracts,
+            storage: vec![],
+            calldata: vec![],
+            gas: 100_000,
+            settings: Settings {
+                default_aa_code_hash: [0; 32],
+                evm_interpreter_code_hash: [0; 32],
+                hook_address: 0,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Runs the first contract and returns the trace.
+    fn run(self) -> String {
+        let address = self.contracts[0].0;
+        let mut world = TestWorld::new(&self.contracts);
+        world.storage.extend(self.storage);
+        let program = initial_decommit(&mut world, address);
+
+        let mut vm = VirtualMachine::new(
+            address,
+            program,
+            Address::zero(),
+            self.calldata,
+            self.gas,
+            self.settings,
+        );
+        let mut tracer = CanonicalTracer::default();
+        let end = vm.run(&mut world, &mut tracer);
+        writeln!(tracer.trace, "end {end:?}").unwrap();
+        tracer.trace
+    }
+}
+
+/// Writes a line per instruction with the gas left after it, followed by what the instruction did.
+#[derive(Default)]
+struct CanonicalTracer {
+    trace: String,
+    instruction: String,
+    effects: Vec<String>,
+}
+
+impl Tracer for CanonicalTracer {
+    fn before_instruction(&mut self, vm: &VirtualMachine, pc: u16) {
+        let frame = &vm.state.current_frame;
+        self.instruction = format!(
+            "{} {:#x}:{pc} {}",
+            vm.state.previous_frames.len(),
+            frame.code_address,
+            frame.program().instructions()[pc as usize].mnemonic()
+        );
+    }
+
+    fn after_instruction(&mut self, vm: &VirtualMachine, _: u16) {
+        writeln!(
+            self.trace,
+            "{} gas={}",
+            self.instruction, vm.state.current_frame.gas
+        )
+        .unwrap();
+        for effect in self.effects.drain(..) {
+            writeln!(self.trace, "    {effect}").unwrap();
+        }
+    }
+
+    fn on_far_call(&mut self, vm: &VirtualMachine, mode: CallingMode) {
+        let frame = &vm.state.current_frame;
+        self.effects.push(format!(
+            "far call {mode:?} to {:#x} with gas {}",
+            frame.code_address, frame.gas
+        ));
+    }
+
+    fn on_far_return(&mut self, _: &VirtualMachine, return_type: ReturnType, data: &[u8]) {
+        self.effects.push(format!(
+            "far return {return_type:?} with {} bytes",
+            data.len()
+        ));
+    }
+
+    fn on_near_call(&mut self, vm: &VirtualMachine, destination: u16) {
+        self.effects.push(format!(
+            "near call to {destination} with gas {}",
+            vm.state.current_frame.gas
+        ));
+    }
+
+    fn on_near_return(&mut self, _: &VirtualMachine, return_type: ReturnType) {
+        self.effects.push(format!("near return {return_type:?}"));
+    }
+
+    fn on_panic(&mut self, _: &VirtualMachine, reason: PanicReason) {
+        self.effects.push(format!("panic {reason:?}"));
+    }
+
+    fn on_storage_write(&mut self, _: &VirtualMachine, write: &StorageWrite) {
+        self.effects.push(format!(
+            "storage write {:#x} {:#x}: {:#x} -> {:#x}, refund {}, pubdata {}",
+            write.contract,
+            write.key,
+            write.previous_value.unwrap(),
+            write.value,
+            write.refund,
+            write.pubdata_change
+        ));
+    }
+
+    fn wants_previous_storage_values(&self) -> bool {
+        true
+    }
+
+    fn on_event(&mut self, _: &VirtualMachine, event: &Event) {
+        self.effects.push(format!(
+            "event {:#x} {:#x} first={}",
+            event.key, event.value, event.is_first
+        ));
+    }
+}
+
+fn check_golden(name: &str, trace: &str) {
+    let path = format!("{}/tests/golden/{name}.trace", env!("CARGO_MANIFEST_DIR"));
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        std::fs::write(&path, trace).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("{path} is missing, run with UPDATE_GOLDENS=1 to create it"));
+    if let Some((line, (expected, actual))) = golden
+        .lines()
+        .zip(trace.lines())
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual)
+    {
+        panic!(
+            "trace differs from {path} at line {}\nexpected: {expected}\n  actual: {actual}\n\
+             run with UPDATE_GOLDENS=1 if the change is intended",
+            line + 1
+        );
+    }
+    assert_eq!(
+        golden.lines().count(),
+        trace.lines().count(),
+        "trace has a different length than {path}, run with UPDATE_GOLDENS=1 if the change is intended"
+    );
+}
+
+fn set(register: Register, value: u16) -> Instruction {
+    Instruction::from_binop::<Add>(
+        Immediate1(value).into(),
+        Register2(Register::new(0)),
+        Register1(register).into(),
+        (),
+        Arguments::new(Predicate::Always, 6),
+        false,
+        false,
+    )
+}
+
+#[test]
+fn far_call_with_event() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let callee_address = Address::from_low_u64_be(ADDRESS_EVENT_WRITER as u64);
+
+    let mut abi = U256::zero();
+    abi.0[3] = 20_000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+
+    let main = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(4),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            ret(),
+            ret(),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+    let callee = Program::new(
+        vec![
+            set(r2, 7),
+            Instruction::from_event(
+                Register1(r2),
+                Register2(r2),
+                true,
+                Arguments::new(Predicate::Always, 100),
+            ),
+            ret(),
+        ],
+        vec![],
+    );
+
+    let trace = Scenario::new(vec![(main_address, main), (callee_address, callee)]).run();
+    check_golden("far_call_with_event", &trace);
+}
+
+#[test]
+fn storage_writes_and_revert() {
+    let r0 = Register::new(0);
+    let key = Register::new(2);
+    let value = Register::new(3);
+
+    let sstore = || {
+        Instruction::from_sstore(
+            Register1(key),
+            Register2(value),
+            Arguments::new(Predicate::Always, 5511),
+        )
+    };
+
+    let program = Program::new(
+        vec![
+            set(key, 1),
+            set(value, 7),
+            sstore(),
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(6),
+                Immediate2(5),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            ret(),
+            ret(),
+            // Overwrites the slot, then reverts
+            set(value, 8),
+            sstore(),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut scenario = Scenario::new(vec![(address, program)]);
+    scenario.storage.push(((address, 1.into()), 5.into()));
+    scenario.gas = 50_000;
+    check_golden("storage_writes_and_revert", &scenario.run());
+}
@@ -0,0 +1,167 @@
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::{Add, Heap},
+    testworld::TestWorld,
+    ExecutionEnd, HookResult, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+const HOOK_ADDRESS: u16 = 0x100;
+
+fn trigger_hook(hook: u16) -> [Instruction; 2] {
+    let r0 = Register::new(0);
+    let r2 = Register::new(2);
+    [
+        Instruction::from_binop::<Add>(
+            Immediate1(hook).into(),
+            Register2(r0),
+            Register1(r2).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        ),
+        Instruction::from_store::<Heap>(
+            Immediate1(HOOK_ADDRESS).into(),
+            Register2(r2),
+            None,
+            Arguments::new(Predicate::Always, 6),
+            true,
+        ),
+    ]
+}
+
+/// Triggers the given hooks in order, then returns.
+fn vm_triggering(hooks: &[u16]) -> (VirtualMachine, TestWorld) {
+    let r0 = Register::new(0);
+    let program = Program::new(
+        hooks
+            .iter()
+            .flat_map(|&hook| trigger_hook(hook))
+            .chain([
+                Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+                Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            ])
+            .collect(),
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+    let vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: HOOK_ADDRESS as u32,
+            ..Default::default()
+        },
+    );
+    (vm, world)
+}
+
+#[test]
+fn handlers_continue_suspend_or_abort() {
+    let (mut vm, mut world) = vm_triggering(&[1, 2, 3]);
+
+    vm.set_hook_handler(1, |vm, _| {
+        vm.state.registers[5] += 1.into();
+        HookResult::Continue
+    });
+    // Hook 2 has no handler, so it suspends.
+    vm.set_hook_handler(3, |_, _| HookResult::Abort);
+
+    assert_eq!(
+        vm.run(&mut world, &mut ()),
+        ExecutionEnd::SuspendedOnHook {
+            hook: 2,
+            pc_to_resume_from: 4
+        }
+    );
+    assert_eq!(vm.state.registers[5], 1.into());
+
+    assert_eq!(
+        vm.resume_from(4, &mut world, &mut ()),
+        ExecutionEnd::AbortedByHook { hook: 3 }
+    );
+
+    // Without its handler, hook 3 suspends too.
+    assert!(vm.remove_hook_handler(3).is_some());
+    assert_eq!(
+        vm.resume_from(4, &mut world, &mut ()),
+        ExecutionEnd::SuspendedOnHook {
+            hook: 3,
+            pc_to_resume_from: 6
+        }
+    );
+    assert_eq!(
+        vm.resume_from(6, &mut world, &mut ()),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+}
+
+#[test]
+fn handlers_can_remove_or_replace_themselves() {
+    let (mut vm, mut world) = vm_triggering(&[1, 2, 2, 1]);
+
+    vm.set_hook_handler(1, |vm, _| {
+        vm.state.registers[5] += 1.into();
+        assert!(vm.remove_hook_handler(1).is_none());
+        HookResult::Continue
+    });
+    vm.set_hook_handler(2, |vm, _| {
+        vm.state.registers[5] += 10.into();
+        vm.set_hook_handler(2, |vm, _| {
+            vm.state.registers[5] += 100.into();
+            HookResult::Continue
+        });
+        HookResult::Continue
+    });
+
+    // The second time hook 1 is triggered, its handler is gone.
+    assert_eq!(
+        vm.run(&mut world, &mut ()),
+        ExecutionEnd::SuspendedOnHook {
+            hook: 1,
+            pc_to_resume_from: 8
+        }
+    );
+    assert_eq!(vm.state.registers[5], 111.into());
+    assert!(vm.remove_hook_handler(1).is_none());
+    assert!(vm.remove_hook_handler(2).is_some());
+}
+
+#[test]
+fn handlers_may_roll_the_vm_back() {
+    let (mut vm, mut world) = vm_triggering(&[1, 2]);
+
+    let mut snapshot = Some(vm.snapshot());
+    vm.set_hook_handler(1, move |vm, _| {
+        if let Some(snapshot) = snapshot.take() {
+            vm.rollback(snapshot);
+        }
+        HookResult::Continue
+    });
+
+    // Execution continues after the first hook in the restored frame,
+    // so only the second hook has been paid for.
+    assert_eq!(
+        vm.run(&mut world, &mut ()),
+        ExecutionEnd::SuspendedOnHook {
+            hook: 2,
+            pc_to_resume_from: 4
+        }
+    );
+    let (mut only_second, mut world) = vm_triggering(&[2]);
+    only_second.run(&mut world, &mut ());
+    assert_eq!(
+        vm.state.current_frame.gas,
+        only_second.state.current_frame.gas
+    );
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::create_looping_vm;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use vm2::ExecutionEnd;
+
+#[test]
+fn execution_can_be_interrupted_from_another_thread() {
+    // Enough gas to loop for much longer than the test is allowed to take
+    let (mut vm, mut world) = create_looping_vm(u32::MAX);
+    let flag = Arc::new(AtomicBool::new(false));
+    vm.set_interrupt_flag(flag.clone());
+
+    let interrupter = {
+        let flag = flag.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            flag.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let ExecutionEnd::Interrupted { pc_to_resume_from } = vm.run(&mut world, &mut ()) else {
+        panic!("execution should have been interrupted");
+    };
+    interrupter.join().unwrap();
+
+    let gas_before = vm.state.current_frame.gas;
+    flag.store(false, Ordering::Relaxed);
+    assert!(matches!(
+        vm.resume_with_instruction_budget(pc_to_resume_from, &mut world, &mut (), 10),
+        ExecutionEnd::BudgetExhausted { .. }
+    ));
+    assert_eq!(vm.state.current_frame.gas, gas_before - 60);
+}
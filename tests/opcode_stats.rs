@@ -0,0 +1,119 @@
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::{Add, Heap},
+    testworld::TestWorld,
+    tracers::OpcodeProfiler,
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::{
+    ethereum_types::Address, AddOpcode, LogOpcode, NearCallOpcode, Opcode, RetOpcode, UMAOpcode,
+};
+
+#[test]
+fn counts_executions_and_gas_per_opcode() {
+    let r0 = Register::new(0);
+    let calldata_pointer = Register::new(1);
+    let r2 = Register::new(2);
+
+    let add = |predicate| {
+        Instruction::from_binop::<Add>(
+            Immediate1(1).into(),
+            Register2(r2),
+            Register1(r2).into(),
+            (),
+            Arguments::new(predicate, 6),
+            false,
+            true,
+        )
+    };
+
+    let program = Program::new(
+        vec![
+            add(Predicate::Always),
+            // The result of the previous addition is not zero
+            add(Predicate::IfEQ),
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(4),
+                Immediate2(5),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_invalid(),
+            // Using a fat pointer as a heap address panics
+            Instruction::from_store::<Heap>(
+                Register1(calldata_pointer).into(),
+                Register2(r0),
+                None,
+                Arguments::new(Predicate::Always, 6),
+                false,
+            ),
+            Instruction::from_sstore(
+                Register1(r2),
+                Register2(r2),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let initial_gas = 10_000;
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        initial_gas,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut profiler = OpcodeProfiler::new();
+    assert_eq!(
+        vm.run(&mut world, &mut profiler),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+    let report = profiler.into_report();
+
+    let add = report.opcodes[&Opcode::Add(AddOpcode::Add)];
+    assert_eq!((add.executed, add.skipped), (1, 1));
+    assert_eq!((add.static_gas, add.dynamic_gas), (12, 0));
+    assert_eq!(report.skipped_by_predicate[&Predicate::IfEQ], 1);
+    assert_eq!(report.skipped_by_predicate.len(), 1);
+
+    let near_call = report.opcodes[&Opcode::NearCall(NearCallOpcode::NearCall)];
+    assert_eq!((near_call.executed, near_call.total_gas()), (1, 25));
+
+    // The panic caused by the store costs as much as a return
+    let store = report.opcodes[&Opcode::UMA(UMAOpcode::HeapWrite)];
+    assert_eq!(
+        (store.executed, store.static_gas, store.dynamic_gas),
+        (1, 6, 5)
+    );
+
+    let sstore = report.opcodes[&Opcode::Log(LogOpcode::StorageWrite)];
+    assert_eq!((sstore.executed, sstore.static_gas), (1, 5511));
+
+    assert_eq!(report.opcodes[&Opcode::Ret(RetOpcode::Ok)].executed, 1);
+    assert_eq!(report.opcodes.len(), 5);
+
+    let total = report
+        .by_total_gas()
+        .iter()
+        .map(|(_, stats)| stats.total_gas())
+        .sum::<i64>();
+    assert_eq!(total, (initial_gas - vm.state.current_frame.gas) as i64);
+    assert_eq!(
+        report.by_total_gas()[0].0,
+        Opcode::Log(LogOpcode::StorageWrite)
+    );
+}
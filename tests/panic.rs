@@ -1,9 +1,10 @@
 use proptest::prelude::*;
 use vm2::{
-    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1},
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
     initial_decommit,
+    instruction_handlers::Heap,
     testworld::TestWorld,
-    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+    ExecutionEnd, Instruction, PanicReason, Predicate, Program, Tracer, VirtualMachine,
 };
 use zkevm_opcode_defs::ethereum_types::Address;
 
@@ -43,14 +44,83 @@ proptest! {
                 default_aa_code_hash: [0; 32],
                 evm_interpreter_code_hash: [0; 32],
                 hook_address: 0,
+                ..Default::default()
             },
         );
 
-        assert_eq!(vm.run(&mut world),
-            if 1 < label && label < 100 {
-                ExecutionEnd::ProgramFinished(vec![])
-            } else {
-                ExecutionEnd::Panicked
+        assert_eq!(vm.run(&mut world, &mut ()),
+            match label {
+                // Calls itself until out of gas
+                0 => ExecutionEnd::Panicked(PanicReason::OutOfGas),
+                1 => ExecutionEnd::Panicked(PanicReason::ExplicitPanic),
+                2..=99 => ExecutionEnd::ProgramFinished(vec![]),
+                _ => ExecutionEnd::Panicked(PanicReason::InvalidJump),
             });
     }
 }
+
+#[derive(Default)]
+struct PanicRecorder(Vec<PanicReason>);
+
+impl Tracer for PanicRecorder {
+    fn on_panic(&mut self, _: &VirtualMachine, reason: PanicReason) {
+        self.0.push(reason);
+    }
+}
+
+#[test]
+fn every_panicking_frame_has_a_reason() {
+    let r0 = Register::new(0);
+    let calldata_pointer = Register::new(1);
+    let program = Program::new(
+        vec![
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(2),
+                Immediate2(1),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_panic(None, Arguments::new(Predicate::Always, 5)),
+            // Using a fat pointer as a heap address is not allowed
+            Instruction::from_store::<Heap>(
+                Register1(calldata_pointer).into(),
+                Register2(r0),
+                None,
+                Arguments::new(Predicate::Always, 6),
+                false,
+            ),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        1000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut tracer = PanicRecorder::default();
+    assert_eq!(
+        vm.run(&mut world, &mut tracer),
+        ExecutionEnd::Panicked(PanicReason::ExplicitPanic)
+    );
+    assert_eq!(
+        tracer.0,
+        vec![
+            PanicReason::IncorrectPointerTags,
+            PanicReason::ExplicitPanic
+        ]
+    );
+}
@@ -0,0 +1,176 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Register, Register1, Register2, RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode, Heap},
+    testworld::TestWorld,
+    tracers::{Revert, RevertCollector},
+    ErrorRegistry, ExecutionEnd, Instruction, Predicate, Program, RevertReason, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+fn error_string(message: &str) -> Vec<u8> {
+    let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+    let mut word = [0; 32];
+    U256::from(32).to_big_endian(&mut word);
+    data.extend(word);
+    U256::from(message.len()).to_big_endian(&mut word);
+    data.extend(word);
+    data.extend(message.as_bytes());
+    data.resize(4 + 64 + message.len().next_multiple_of(32), 0);
+    data
+}
+
+#[test]
+fn decodes_solidity_errors() {
+    let mut registry = ErrorRegistry::default();
+    registry.register([0xde, 0xad, 0xbe, 0xef], "Unauthorized(address)");
+
+    assert_eq!(RevertReason::decode(&[], &registry), RevertReason::Empty);
+    assert_eq!(
+        RevertReason::decode(&error_string("not enough funds"), &registry),
+        RevertReason::Error("not enough funds".into())
+    );
+
+    let mut panic = vec![0x4e, 0x48, 0x7b, 0x71];
+    panic.extend([0; 31]);
+    panic.push(0x11);
+    let reason = RevertReason::decode(&panic, &registry);
+    assert_eq!(reason, RevertReason::Panic(0x11.into()));
+    assert_eq!(
+        reason.to_string(),
+        "Panic(0x11): arithmetic overflow or underflow"
+    );
+
+    let custom = [0xde, 0xad, 0xbe, 0xef, 1, 2];
+    assert_eq!(
+        RevertReason::decode(&custom, &registry),
+        RevertReason::Custom {
+            signature: "Unauthorized(address)".into(),
+            arguments: vec![1, 2]
+        }
+    );
+
+    // Truncated Error(string)
+    let truncated = &error_string("hello")[..40];
+    assert_eq!(
+        RevertReason::decode(truncated, &registry),
+        RevertReason::Unknown(truncated.to_vec())
+    );
+    assert_eq!(
+        RevertReason::decode(&[1, 2, 3], &registry),
+        RevertReason::Unknown(vec![1, 2, 3])
+    );
+}
+
+#[test]
+fn collects_caught_reverts() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let revert_data = error_string("nope");
+    let mut words: Vec<U256> = revert_data
+        .chunks(32)
+        .map(|chunk| {
+            let mut word = [0; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            U256::from_big_endian(&word)
+        })
+        .collect();
+    let word_count = words.len() as u16;
+    // Points to the start of the heap
+    let mut revert_abi = U256::zero();
+    revert_abi.0[1] = (revert_data.len() as u64) << 32;
+    words.push(revert_abi);
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+
+    let mut callee_instructions = vec![];
+    for i in 0..word_count {
+        callee_instructions.push(load_from_code_page(i, r1));
+        callee_instructions.push(Instruction::from_store::<Heap>(
+            Immediate1(i * 32).into(),
+            Register2(r1),
+            None,
+            Arguments::new(Predicate::Always, 6),
+            false,
+        ));
+    }
+    callee_instructions.push(load_from_code_page(word_count, r1));
+    callee_instructions.push(Instruction::from_revert(
+        Register1(r1),
+        None,
+        Arguments::new(Predicate::Always, 5),
+    ));
+    let callee = Program::new(callee_instructions, words);
+
+    let callee_address = Address::from_low_u64_be(0xca11ee);
+    let mut abi = U256::zero();
+    abi.0[3] = 5000;
+    let main_program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(3),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            // The revert is caught and the program finishes normally
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+
+    let main_address = Address::from_low_u64_be(0xfeddeadbeef);
+    let mut world = TestWorld::new(&[(main_address, main_program), (callee_address, callee)]);
+    let program = initial_decommit(&mut world, main_address);
+
+    let mut vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        10_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut collector = RevertCollector::default();
+    assert_eq!(
+        vm.run(&mut world, &mut collector),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+    assert_eq!(
+        collector.reverts(),
+        [Revert {
+            address: callee_address,
+            code_address: callee_address,
+            depth: 1,
+            reason: RevertReason::Error("nope".into()),
+        }]
+    );
+}
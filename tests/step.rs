@@ -0,0 +1,135 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Immediate2, Register, Register1, Register2,
+        RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode},
+    testworld::TestWorld,
+    Instruction, Predicate, Program, Step, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+const MAIN_ADDRESS: u64 = 0xfeddeadbeef;
+const CALLEE_ADDRESS: u64 = 0xca11ee;
+
+fn programs() -> Vec<(Address, Program)> {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    let callee_address = Address::from_low_u64_be(CALLEE_ADDRESS);
+    let mut abi = U256::zero();
+    abi.0[3] = 5000;
+
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+
+    let main_program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(4),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![abi, U256::from_big_endian(callee_address.as_bytes())],
+    );
+
+    let callee = Program::new(
+        vec![
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(2),
+                Immediate2(3),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            Instruction::from_sstore(
+                Register1(r0),
+                Register2(r0),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    vec![
+        (Address::from_low_u64_be(MAIN_ADDRESS), main_program),
+        (callee_address, callee),
+    ]
+}
+
+fn create_vm(programs: &[(Address, Program)], gas: u32) -> (VirtualMachine, TestWorld) {
+    let main_address = Address::from_low_u64_be(MAIN_ADDRESS);
+    let mut world = TestWorld::new(programs);
+    let program = initial_decommit(&mut world, main_address);
+
+    let vm = VirtualMachine::new(
+        main_address,
+        program,
+        Address::zero(),
+        vec![],
+        gas,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    (vm, world)
+}
+
+#[test]
+fn stepping_is_equivalent_to_running() {
+    // Programs are compared by identity, so both VMs need to share them.
+    let programs = programs();
+
+    // The lower gas limits make the callee run out of gas at various points.
+    for gas in [10_000, 5_300, 300, 100, 0] {
+        let (mut vm, mut world) = create_vm(&programs, gas);
+        let expected = vm.run(&mut world, &mut ());
+
+        let (mut stepped_vm, mut world) = create_vm(&programs, gas);
+        let mut pc = 0;
+        let mut entered_callee = false;
+        let end = loop {
+            match stepped_vm.step(pc, &mut world, &mut ()) {
+                Step::Continue { pc: next, frame } => {
+                    entered_callee |= frame == 1;
+                    pc = next;
+                }
+                Step::End(end) => break end,
+            }
+        };
+
+        assert_eq!(end, expected);
+        assert_eq!(stepped_vm.state, vm.state);
+        if gas == 10_000 {
+            assert!(entered_callee);
+        }
+    }
+}
@@ -113,10 +113,11 @@ fn test_scenario(gas_to_pass: u32) -> (ExecutionEnd, u32) {
             default_aa_code_hash: [0; 32],
             evm_interpreter_code_hash: intepreter_hash,
             hook_address: 0,
+            ..Default::default()
         },
     );
 
-    let result = vm.run(&mut world);
+    let result = vm.run(&mut world, &mut ());
     (result, vm.state.current_frame.gas)
 }
 
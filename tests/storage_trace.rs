@@ -0,0 +1,229 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::Add,
+    testworld::TestWorld,
+    tracers::{StorageAccess, StorageAccessKind, StorageTracer},
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine, World,
+};
+use zkevm_opcode_defs::ethereum_types::{Address, H160};
+
+#[test]
+fn records_accesses_and_rollbacks() {
+    let r0 = Register::new(0);
+    let key = Register::new(2);
+    let value = Register::new(3);
+    let out = Register::new(4);
+
+    let set = |register, immediate| {
+        Instruction::from_binop::<Add>(
+            Immediate1(immediate).into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let sload = || {
+        Instruction::from_sload(
+            Register1(key),
+            Register1(out),
+            Arguments::new(Predicate::Always, 2008),
+        )
+    };
+
+    let program = Program::new(
+        vec![
+            set(key, 1),
+            sload(),
+            sload(),
+            set(value, 7),
+            Instruction::from_sstore(
+                Register1(key),
+                Register2(value),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(8),
+                Immediate2(6),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_sstore_transient(
+                Register1(key),
+                Register2(value),
+                Arguments::new(Predicate::Always, 5),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            // Writes to slot 7, then reverts
+            Instruction::from_sstore(
+                Register1(value),
+                Register2(value),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut tracer = StorageTracer::new();
+    assert_eq!(
+        vm.run(&mut world, &mut tracer),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+    let accesses = tracer.into_accesses();
+
+    let access = |kind, key: u64, previous_value: u64, value: u64| StorageAccess {
+        kind,
+        contract: address,
+        key: key.into(),
+        previous_value: previous_value.into(),
+        value: value.into(),
+        warm: false,
+        refund: 0,
+        pubdata_change: 0,
+        rolled_back: false,
+    };
+
+    assert_eq!(accesses.len(), 5);
+    assert_eq!(accesses[0], access(StorageAccessKind::Read, 1, 0, 0));
+
+    assert!(accesses[1].warm);
+    assert_ne!(accesses[1].refund, 0);
+    assert_eq!(accesses[1].value, U256::zero());
+
+    // Writing a slot that has only been read is still a cold write but costs less
+    assert!(!accesses[2].warm);
+    assert_ne!(accesses[2].refund, 0);
+    assert_eq!(
+        (
+            accesses[2].previous_value,
+            accesses[2].value,
+            accesses[2].pubdata_change
+        ),
+        (U256::zero(), U256::from(7), 50)
+    );
+
+    assert_eq!(
+        accesses[3],
+        StorageAccess {
+            pubdata_change: 50,
+            rolled_back: true,
+            ..access(StorageAccessKind::Write, 7, 0, 7)
+        }
+    );
+    assert_eq!(
+        accesses[4],
+        StorageAccess {
+            warm: true,
+            ..access(StorageAccessKind::TransientWrite, 1, 0, 7)
+        }
+    );
+}
+
+/// A [TestWorld] in which every storage slot is free.
+struct FreeStorage(TestWorld);
+
+impl World for FreeStorage {
+    fn decommit(&mut self, hash: U256) -> Program {
+        self.0.decommit(hash)
+    }
+
+    fn read_storage(&mut self, contract: H160, key: U256) -> U256 {
+        self.0.read_storage(contract, key)
+    }
+
+    fn cost_of_writing_storage(&mut self, contract: H160, key: U256, new_value: U256) -> u32 {
+        self.0.cost_of_writing_storage(contract, key, new_value)
+    }
+
+    fn is_free_storage_slot(&self, _: &H160, _: &U256) -> bool {
+        true
+    }
+}
+
+#[test]
+fn free_slots_are_cold_until_accessed() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let sload = || {
+        Instruction::from_sload(
+            Register1(r0),
+            Register1(r1),
+            Arguments::new(Predicate::Always, 2008),
+        )
+    };
+    let sstore = || {
+        Instruction::from_sstore(
+            Register1(r0),
+            Register2(r0),
+            Arguments::new(Predicate::Always, 5511),
+        )
+    };
+    let program = Program::new(
+        vec![
+            sload(),
+            sload(),
+            sstore(),
+            sstore(),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = FreeStorage(TestWorld::new(&[(address, program)]));
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut tracer = StorageTracer::new();
+    assert_eq!(
+        vm.run(&mut world, &mut tracer),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    // Every access is refunded, but only repeated ones are warm.
+    let accesses = tracer.into_accesses();
+    assert!(accesses.iter().all(|access| access.refund != 0));
+    assert_eq!(
+        accesses
+            .iter()
+            .map(|access| access.warm)
+            .collect::<Vec<_>>(),
+        vec![false, true, false, true]
+    );
+}
@@ -0,0 +1,84 @@
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::Add,
+    testworld::TestWorld,
+    tracers::StructLogger,
+    ExecutionEnd, Instruction, Predicate, Program, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn one_json_object_per_instruction() {
+    let r0 = Register::new(0);
+    let r2 = Register::new(2);
+    let program = Program::new(
+        vec![
+            Instruction::from_binop::<Add>(
+                Immediate1(42).into(),
+                Register2(r2),
+                Register1(r2).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                true,
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        1000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut logger = StructLogger::new(vec![]);
+    assert_eq!(
+        vm.run(&mut world, &mut logger),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+    let output = String::from_utf8(logger.finish().unwrap()).unwrap();
+    let lines = output.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 2);
+
+    let register = |value: &str, is_pointer: bool| {
+        format!(r#"{{"value":"{value}","isPointer":{is_pointer}}}"#)
+    };
+    let registers = |r2: &str| {
+        let mut registers = vec![register("0x0", false); 16];
+        // The calldata pointer to the empty calldata on heap 1
+        registers[1] = register("0x100000000", true);
+        registers[2] = register(r2, false);
+        registers.join(",")
+    };
+
+    assert_eq!(
+        lines[0],
+        format!(
+            r#"{{"pc":0,"op":"add","gas":1000,"gasCost":6,"depth":1,"nearCallDepth":0,"registers":[{}],"flags":{{"lt":false,"eq":false,"gt":false}}}}"#,
+            registers("0x0")
+        )
+    );
+    assert_eq!(
+        lines[1],
+        format!(
+            r#"{{"pc":1,"op":"ret","gas":994,"gasCost":5,"depth":1,"nearCallDepth":0,"registers":[{}],"flags":{{"lt":false,"eq":false,"gt":true}}}}"#,
+            registers("0x2a")
+        )
+    );
+}
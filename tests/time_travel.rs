@@ -0,0 +1,150 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::{Add, Heap},
+    testworld::TestWorld,
+    ExecutionEnd, Instruction, Predicate, Program, Step, TimeTravel, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+fn vm_with(program: Vec<Instruction>) -> (VirtualMachine, TestWorld) {
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, Program::new(program, vec![]))]);
+    let program = initial_decommit(&mut world, address);
+
+    let vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+    (vm, world)
+}
+
+#[test]
+fn goes_back_and_forth() {
+    let r0 = Register::new(0);
+    let r2 = Register::new(2);
+
+    let increment = || {
+        Instruction::from_binop::<Add>(
+            Immediate1(1).into(),
+            Register2(r2),
+            Register1(r2).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let ret = || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+    let (mut vm, mut world) = vm_with(vec![
+        increment(),
+        increment(),
+        Instruction::from_near_call(
+            Register1(r0),
+            Immediate1(5),
+            Immediate2(3),
+            Arguments::new(Predicate::Always, 25),
+        ),
+        increment(),
+        ret(),
+        // Writes a slot, then reverts
+        Instruction::from_sstore(
+            Register1(r2),
+            Register2(r2),
+            Arguments::new(Predicate::Always, 5511),
+        ),
+        increment(),
+        Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+    ]);
+
+    let mut time_travel = TimeTravel::new(&vm, 0, 2);
+    while let Step::Continue { .. } = time_travel.step(&mut vm, &mut world, &mut ()) {}
+    assert_eq!(time_travel.position(), 8);
+    assert_eq!(vm.state.registers[2], U256::from(4));
+    assert!(vm.world_diff.get_storage_state().is_empty());
+
+    // The checkpoint inside the near call is gone, so this replays from an earlier one.
+    assert_eq!(
+        time_travel.go_to(&mut vm, &mut world, &mut (), 4),
+        Some(Step::Continue { pc: 6, frame: 0 })
+    );
+    assert_eq!(time_travel.position(), 4);
+    assert_eq!(vm.state.registers[2], U256::from(2));
+    assert_eq!(
+        vm.world_diff
+            .get_storage_state()
+            .values()
+            .collect::<Vec<_>>(),
+        vec![&U256::from(2)]
+    );
+
+    assert_eq!(
+        time_travel.go_to(&mut vm, &mut world, &mut (), 1),
+        Some(Step::Continue { pc: 1, frame: 0 })
+    );
+    assert_eq!(vm.state.registers[2], U256::one());
+    assert!(vm.world_diff.get_storage_state().is_empty());
+
+    assert_eq!(
+        time_travel.go_to(&mut vm, &mut world, &mut (), 100),
+        Some(Step::End(ExecutionEnd::ProgramFinished(vec![])))
+    );
+    assert_eq!(time_travel.position(), 8);
+    assert_eq!(vm.state.registers[2], U256::from(4));
+    assert!(vm.world_diff.get_storage_state().is_empty());
+}
+
+#[test]
+fn restores_heaps() {
+    let r0 = Register::new(0);
+    let r2 = Register::new(2);
+
+    // Keeps growing the heap and overwriting words written earlier.
+    let mut program = vec![];
+    for i in 0..20 {
+        program.push(Instruction::from_binop::<Add>(
+            Immediate1(1).into(),
+            Register2(r2),
+            Register1(r2).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        ));
+        program.push(Instruction::from_store::<Heap>(
+            Immediate1(i % 7 * 100).into(),
+            Register2(r2),
+            None,
+            Arguments::new(Predicate::Always, 6),
+            false,
+        ));
+    }
+    program.push(Instruction::from_ret(
+        Register1(r0),
+        None,
+        Arguments::new(Predicate::Always, 5),
+    ));
+    let (mut vm, mut world) = vm_with(program);
+
+    let mut time_travel = TimeTravel::new(&vm, 0, 1);
+    let mut states = vec![vm.state.clone()];
+    while let Step::Continue { .. } = time_travel.step(&mut vm, &mut world, &mut ()) {
+        states.push(vm.state.clone());
+    }
+
+    for position in (0..states.len()).rev().step_by(3) {
+        time_travel.go_to(&mut vm, &mut world, &mut (), position as u64);
+        assert_eq!(vm.state, states[position]);
+    }
+}
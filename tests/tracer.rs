@@ -0,0 +1,110 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{Arguments, Immediate1, Immediate2, Register, Register1, Register2},
+    initial_decommit,
+    instruction_handlers::ReturnType,
+    testworld::TestWorld,
+    ExecutionEnd, Instruction, Predicate, Program, StorageWrite, Tracer, VirtualMachine,
+};
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[derive(Debug, PartialEq)]
+enum Observation {
+    Before(u16),
+    After(u16),
+    NearCall(u16),
+    NearReturn(ReturnType),
+    FarReturn(ReturnType),
+    StorageWrite(U256, U256),
+}
+
+#[derive(Default)]
+struct RecordingTracer(Vec<Observation>);
+
+impl Tracer for RecordingTracer {
+    fn before_instruction(&mut self, _: &VirtualMachine, pc: u16) {
+        self.0.push(Observation::Before(pc));
+    }
+    fn after_instruction(&mut self, _: &VirtualMachine, pc: u16) {
+        self.0.push(Observation::After(pc));
+    }
+    fn on_near_call(&mut self, _: &VirtualMachine, destination: u16) {
+        self.0.push(Observation::NearCall(destination));
+    }
+    fn on_near_return(&mut self, _: &VirtualMachine, return_type: ReturnType) {
+        self.0.push(Observation::NearReturn(return_type));
+    }
+    fn on_far_return(&mut self, _: &VirtualMachine, return_type: ReturnType, _: &[u8]) {
+        self.0.push(Observation::FarReturn(return_type));
+    }
+    fn on_storage_write(&mut self, _: &VirtualMachine, write: &StorageWrite) {
+        self.0
+            .push(Observation::StorageWrite(write.key, write.value));
+    }
+}
+
+#[test]
+fn tracer_sees_calls_returns_and_storage() {
+    let r0 = Register::new(0);
+    let program = Program::new(
+        vec![
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(2),
+                Immediate2(0xFFFF),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+            Instruction::from_sstore(
+                Register1(r0),
+                Register2(r0),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        10_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut tracer = RecordingTracer::default();
+    assert_eq!(
+        vm.run(&mut world, &mut tracer),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+
+    use Observation::*;
+    assert_eq!(
+        tracer.0,
+        vec![
+            Before(0),
+            NearCall(2),
+            After(0),
+            Before(2),
+            StorageWrite(U256::zero(), U256::zero()),
+            After(2),
+            Before(3),
+            NearReturn(ReturnType::Normal),
+            After(3),
+            Before(1),
+            FarReturn(ReturnType::Normal),
+            After(1),
+        ]
+    );
+}
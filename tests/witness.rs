@@ -0,0 +1,313 @@
+use u256::U256;
+use vm2::{
+    addressing_modes::{
+        Arguments, CodePage, Immediate1, Immediate2, Register, Register1, Register2,
+        RegisterAndImmediate,
+    },
+    initial_decommit,
+    instruction_handlers::{Add, CallingMode, Heap},
+    testworld::TestWorld,
+    tracers::WitnessRecorder,
+    ExecutionEnd, Instruction, PanicReason, Predicate, Program, VirtualMachine,
+    INITIAL_MONOTONIC_CYCLE_COUNTER, STARTING_TIMESTAMP, TIME_DELTA_PER_CYCLE,
+};
+use zk_evm_abstractions::aux::MemoryType;
+use zkevm_opcode_defs::ethereum_types::Address;
+
+#[test]
+fn records_memory_and_log_queries() {
+    let r0 = Register::new(0);
+    let r3 = Register::new(3);
+    let r4 = Register::new(4);
+
+    let sstore = |key| {
+        Instruction::from_sstore(
+            Register1(key),
+            Register2(r3),
+            Arguments::new(Predicate::Always, 5511),
+        )
+    };
+    let ret = || Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5));
+
+    let program = Program::new(
+        vec![
+            Instruction::from_binop::<Add>(
+                Immediate1(7).into(),
+                Register2(r0),
+                Register1(r3).into(),
+                (),
+                Arguments::new(Predicate::Always, 6),
+                false,
+                false,
+            ),
+            // Not aligned, so two words are written
+            Instruction::from_store::<Heap>(
+                Immediate1(40).into(),
+                Register2(r3),
+                None,
+                Arguments::new(Predicate::Always, 6),
+                false,
+            ),
+            Instruction::from_load::<Heap>(
+                Immediate1(64).into(),
+                Register1(r4),
+                None,
+                Arguments::new(Predicate::Always, 6),
+            ),
+            sstore(r3),
+            Instruction::from_near_call(
+                Register1(r0),
+                Immediate1(7),
+                Immediate2(6),
+                Arguments::new(Predicate::Always, 25),
+            ),
+            ret(),
+            ret(),
+            // Writes a slot, then reverts
+            sstore(r4),
+            Instruction::from_revert(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut recorder = WitnessRecorder::new();
+    assert_eq!(
+        vm.run(&mut world, &mut recorder),
+        ExecutionEnd::ProgramFinished(vec![])
+    );
+    let queries = recorder.into_queries();
+
+    let heap = vm.state.current_frame.heap;
+    let shifted_seven = U256::from(7) << 192;
+    assert_eq!(
+        queries
+            .memory
+            .iter()
+            .map(|(_, query)| (
+                query.location.memory_type,
+                query.location.page.0,
+                query.location.index.0,
+                query.value,
+                query.rw_flag
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (MemoryType::Heap, heap, 1, U256::zero(), true),
+            (MemoryType::Heap, heap, 2, shifted_seven, true),
+            (MemoryType::Heap, heap, 2, shifted_seven, false),
+        ]
+    );
+
+    assert_eq!(
+        queries
+            .log
+            .iter()
+            .map(|(_, query)| (
+                query.key,
+                query.read_value,
+                query.written_value,
+                query.rw_flag,
+                query.rollback
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (7.into(), 0.into(), 7.into(), true, false),
+            (shifted_seven, 0.into(), 7.into(), true, false),
+            (shifted_seven, 0.into(), 7.into(), true, true),
+        ]
+    );
+    assert!(queries
+        .log
+        .iter()
+        .all(|(_, query)| query.address == address));
+
+    // Eight instructions were executed. The write is made by the second and the read by the third.
+    assert_eq!(
+        vm.state.monotonic_cycle_counter,
+        INITIAL_MONOTONIC_CYCLE_COUNTER + 8
+    );
+    assert_eq!(
+        vm.state.timestamp,
+        STARTING_TIMESTAMP + 8 * TIME_DELTA_PER_CYCLE
+    );
+    assert_eq!(
+        queries
+            .memory
+            .iter()
+            .map(|(_, query)| query.timestamp.0)
+            .collect::<Vec<_>>(),
+        vec![
+            STARTING_TIMESTAMP + TIME_DELTA_PER_CYCLE + 2,
+            STARTING_TIMESTAMP + TIME_DELTA_PER_CYCLE + 2,
+            STARTING_TIMESTAMP + 2 * TIME_DELTA_PER_CYCLE + 1,
+        ]
+    );
+    assert_eq!(
+        queries.log[0].1.timestamp.0,
+        STARTING_TIMESTAMP + 3 * TIME_DELTA_PER_CYCLE + 1
+    );
+
+    fn cycles<T>(queries: &[(u32, T)]) -> Vec<u32> {
+        queries
+            .iter()
+            .map(|(cycle, _)| cycle - INITIAL_MONOTONIC_CYCLE_COUNTER)
+            .collect()
+    }
+    assert_eq!(cycles(&queries.memory), vec![1, 1, 2]);
+    // The rollback happens when the near call reverts.
+    assert_eq!(cycles(&queries.log), vec![3, 5, 6]);
+    assert!(queries.decommittment.is_empty());
+    assert!(queries.precompile.is_empty());
+}
+
+#[test]
+fn timestamps_keep_growing_after_rollback() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let program = Program::new(
+        vec![
+            Instruction::from_precompile_call(
+                Register1(r0),
+                Register2(r0),
+                Register1(r1),
+                Arguments::new(Predicate::Always, 6),
+            ),
+            Instruction::from_ret(Register1(r0), None, Arguments::new(Predicate::Always, 5)),
+        ],
+        vec![],
+    );
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut run = |vm: &mut VirtualMachine| {
+        let mut recorder = WitnessRecorder::new();
+        assert_eq!(
+            vm.run(&mut world, &mut recorder),
+            ExecutionEnd::ProgramFinished(vec![])
+        );
+        recorder.into_queries().precompile[0]
+    };
+
+    let snapshot = vm.snapshot();
+    let (first_cycle, first) = run(&mut vm);
+    vm.rollback(snapshot);
+    let (second_cycle, second) = run(&mut vm);
+
+    assert!(second.timestamp > first.timestamp);
+    assert!(second_cycle > first_cycle);
+}
+
+#[test]
+fn panics_after_failed_far_calls_and_invalid_jumps_take_a_cycle() {
+    let r0 = Register::new(0);
+    let r1 = Register::new(1);
+    let r2 = Register::new(2);
+
+    // Forwarding a fat pointer without having one is invalid
+    let mut invalid_abi = U256::zero();
+    invalid_abi.0[3] = 1000 | (1 << 32);
+
+    let address = Address::from_low_u64_be(0x1234567890abcdef);
+    let load_from_code_page = |immediate, register| {
+        Instruction::from_binop::<Add>(
+            CodePage(RegisterAndImmediate {
+                immediate,
+                register: r0,
+            })
+            .into(),
+            Register2(r0),
+            Register1(register).into(),
+            (),
+            Arguments::new(Predicate::Always, 6),
+            false,
+            false,
+        )
+    };
+    let program = Program::new(
+        vec![
+            load_from_code_page(0, r1),
+            load_from_code_page(1, r2),
+            Instruction::from_far_call::<{ CallingMode::Normal as u8 }>(
+                Register1(r1),
+                Register2(r2),
+                Immediate1(3),
+                false,
+                Arguments::new(Predicate::Always, 200),
+            ),
+            Instruction::from_sstore(
+                Register1(r0),
+                Register2(r0),
+                Arguments::new(Predicate::Always, 5511),
+            ),
+            Instruction::from_jump(Immediate1(100).into(), Arguments::new(Predicate::Always, 6)),
+        ],
+        vec![invalid_abi, U256::from_big_endian(address.as_bytes())],
+    );
+
+    let mut world = TestWorld::new(&[(address, program)]);
+    let program = initial_decommit(&mut world, address);
+    let mut vm = VirtualMachine::new(
+        address,
+        program,
+        Address::zero(),
+        vec![],
+        100_000,
+        vm2::Settings {
+            default_aa_code_hash: [0; 32],
+            evm_interpreter_code_hash: [0; 32],
+            hook_address: 0,
+            ..Default::default()
+        },
+    );
+
+    let mut recorder = WitnessRecorder::new();
+    assert_eq!(
+        vm.run(&mut world, &mut recorder),
+        ExecutionEnd::Panicked(PanicReason::InvalidJump)
+    );
+    let queries = recorder.into_queries();
+
+    // The far call is the third instruction and its panic the fourth.
+    assert_eq!(
+        queries.decommittment[0].0,
+        INITIAL_MONOTONIC_CYCLE_COUNTER + 2
+    );
+    assert_eq!(queries.log[0].0, INITIAL_MONOTONIC_CYCLE_COUNTER + 4);
+    // Then come the jump and its panic.
+    assert_eq!(
+        vm.state.monotonic_cycle_counter,
+        INITIAL_MONOTONIC_CYCLE_COUNTER + 7
+    );
+}